@@ -0,0 +1,105 @@
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+
+// Tokens distributed by Merkle proofs. Leaves are `sha256("{account_id}:{amount}")`, nodes are
+// `sha256` of their two children concatenated in ascending order. Tokens are held in escrow
+// by the token account until claimed or the airdrop is closed.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Airdrop {
+    pub root: Vec<u8>,
+    pub total: Balance,
+    pub claimed: Balance,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AirdropOutput {
+    pub root: Base64VecU8,
+    pub total: U128,
+    pub claimed: U128,
+}
+
+fn airdrop_leaf(account_id: &str, amount: Balance) -> Vec<u8> {
+    env::sha256(format!("{}:{}", account_id, amount).as_bytes())
+}
+
+// Checks that the leaf is part of the tree with the given root.
+fn verify_proof(root: &[u8], leaf: Vec<u8>, proof: &[Base64VecU8]) -> bool {
+    let hash = proof.iter().fold(leaf, |hash, node| {
+        let node = &node.0;
+        if hash <= *node {
+            env::sha256(&[hash.as_slice(), node.as_slice()].concat())
+        } else {
+            env::sha256(&[node.as_slice(), hash.as_slice()].concat())
+        }
+    });
+    hash == root
+}
+
+#[near_bindgen]
+impl Contract {
+    // Starts an airdrop of `total` owner's tokens to the accounts of the tree with the given root.
+    // Only one airdrop can be active at a time.
+    pub fn create_airdrop(&mut self, root: Base64VecU8, total: U128) {
+        self.assert_owner();
+        assert!(self.airdrop.is_none(), "Airdrop is already active");
+        assert_eq!(root.0.len(), 32, "Root has to be 32 bytes");
+        assert!(total.0 > 0, "The amount should be a positive number");
+        let owner_id = self.owner_id.clone();
+        self.internal_escrow_transfer(&owner_id, &env::current_account_id(), total.0, "Airdrop");
+        self.airdrop = Some(Airdrop {
+            root: root.into(),
+            total: total.into(),
+            claimed: 0,
+        });
+    }
+
+    // Claims the caller's share of the active airdrop. The caller must be registered.
+    // Storage of the claim record is paid from the attached deposit.
+    #[payable]
+    pub fn claim_airdrop(&mut self, proof: Vec<Base64VecU8>, amount: U128) {
+        let account_id = env::predecessor_account_id();
+        self.assert_transfer_allowed(&env::current_account_id(), &account_id);
+        let initial_storage_usage = env::storage_usage();
+        let mut airdrop = self.airdrop.take().expect("No active airdrop");
+        assert!(
+            verify_proof(&airdrop.root, airdrop_leaf(&account_id, amount.0), &proof),
+            "Invalid proof"
+        );
+        assert!(
+            self.airdrop_claims.insert(&(airdrop.root.clone(), account_id.clone())),
+            "Airdrop is already claimed"
+        );
+        airdrop.claimed += amount.0;
+        assert!(airdrop.claimed <= airdrop.total, "Airdrop total exceeded");
+        self.airdrop = Some(airdrop);
+        self.internal_escrow_transfer(&env::current_account_id(), &account_id, amount.0, "Airdrop claim");
+        refund_deposit(initial_storage_usage);
+    }
+
+    // Ends the active airdrop, returning unclaimed tokens to the owner.
+    pub fn close_airdrop(&mut self) -> U128 {
+        self.assert_owner();
+        let airdrop = self.airdrop.take().expect("No active airdrop");
+        let unclaimed = airdrop.total - airdrop.claimed;
+        let owner_id = self.owner_id.clone();
+        self.internal_escrow_transfer(&env::current_account_id(), &owner_id, unclaimed, "Airdrop closed");
+        unclaimed.into()
+    }
+
+    pub fn get_airdrop(&self) -> Option<AirdropOutput> {
+        self.airdrop.as_ref().map(|airdrop| AirdropOutput {
+            root: airdrop.root.clone().into(),
+            total: airdrop.total.into(),
+            claimed: airdrop.claimed.into(),
+        })
+    }
+
+    pub fn is_airdrop_claimed(&self, account_id: ValidAccountId) -> bool {
+        let account_id: AccountId = account_id.into();
+        self.airdrop.iter().any(|airdrop| {
+            self.airdrop_claims
+                .contains(&(airdrop.root.clone(), account_id.clone()))
+        })
+    }
+}
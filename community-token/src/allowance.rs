@@ -0,0 +1,73 @@
+use crate::*;
+
+impl Contract {
+    // Replaces the allowance, removing it when set to 0.
+    pub(crate) fn internal_approve(&mut self, owner_id: AccountId, spender_id: AccountId, amount: Balance) {
+        assert_ne!(owner_id, spender_id, "Owner and spender should be different");
+        let key = (owner_id, spender_id);
+        if amount == 0 {
+            self.allowances.remove(&key);
+        } else {
+            self.allowances.insert(&key, &amount);
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Allows the spender to transfer up to `amount` of the caller's tokens with `ft_transfer_from`,
+    // replacing the previous allowance. Storage of a new allowance is paid from the attached deposit,
+    // setting it to 0 removes it and refunds the storage. Requires at least 1 yoctoNEAR.
+    #[payable]
+    pub fn ft_approve(&mut self, spender_id: ValidAccountId, amount: U128) {
+        assert!(
+            env::attached_deposit() >= 1,
+            "Requires attached deposit of at least 1 yoctoNEAR"
+        );
+        let initial_storage_usage = env::storage_usage();
+        self.internal_approve(env::predecessor_account_id(), spender_id.into(), amount.into());
+        refund_deposit(initial_storage_usage);
+    }
+
+    // Transfers tokens of the owner on behalf of the caller, spending the caller's allowance.
+    // A fully spent allowance is removed and its storage refunded to the owner.
+    #[payable]
+    pub fn ft_transfer_from(
+        &mut self,
+        owner_id: ValidAccountId,
+        receiver_id: ValidAccountId,
+        amount: U128,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        let spender_id = env::predecessor_account_id();
+        let owner_id: AccountId = owner_id.into();
+        let receiver_id: AccountId = receiver_id.into();
+        self.assert_transfer_allowed(&owner_id, &receiver_id);
+        assert!(
+            !self.frozen_accounts.contains(&spender_id),
+            "The account {} is frozen",
+            spender_id
+        );
+        let key = (owner_id.clone(), spender_id);
+        let allowance = self.allowances.get(&key).unwrap_or(0);
+        assert!(amount.0 <= allowance, "Allowance exceeded");
+        let initial_storage_usage = env::storage_usage();
+        self.internal_approve(owner_id.clone(), key.1, allowance - amount.0);
+        let freed_deposit = Balance::from(initial_storage_usage - env::storage_usage())
+            * env::storage_byte_cost();
+        if freed_deposit > 0 {
+            Promise::new(owner_id.clone()).transfer(freed_deposit);
+        }
+        self.internal_update_snapshots(&[&owner_id, &receiver_id]);
+        self.token
+            .internal_transfer(&owner_id, &receiver_id, amount.into(), memo);
+    }
+
+    pub fn ft_allowance(&self, owner_id: ValidAccountId, spender_id: ValidAccountId) -> U128 {
+        self.allowances
+            .get(&(owner_id.into(), spender_id.into()))
+            .unwrap_or(0)
+            .into()
+    }
+}
@@ -0,0 +1,46 @@
+use crate::*;
+use near_sdk::Promise;
+
+// Max number of transfers in one `ft_batch_transfer` call, to stay within the gas limit.
+pub const MAX_BATCH_TRANSFER_SIZE: usize = 100;
+
+#[near_bindgen]
+impl Contract {
+    // Transfers tokens from the caller to each of the receivers. Unregistered receivers are
+    // registered with the attached deposit, the rest of it is refunded. Requires at least 1 yoctoNEAR.
+    #[payable]
+    pub fn ft_batch_transfer(&mut self, transfers: Vec<(ValidAccountId, U128)>, memo: Option<String>) {
+        let attached_deposit = env::attached_deposit();
+        assert!(attached_deposit >= 1, "Requires attached deposit of at least 1 yoctoNEAR");
+        assert!(!transfers.is_empty(), "Transfers can't be empty");
+        assert!(
+            transfers.len() <= MAX_BATCH_TRANSFER_SIZE,
+            "Too many transfers, max is {}",
+            MAX_BATCH_TRANSFER_SIZE
+        );
+        let sender_id = env::predecessor_account_id();
+        let registration_cost =
+            Balance::from(self.token.account_storage_usage) * env::storage_byte_cost();
+        let mut storage_cost: Balance = 0;
+        for (receiver_id, amount) in transfers {
+            let receiver_id: AccountId = receiver_id.into();
+            self.assert_transfer_allowed(&sender_id, &receiver_id);
+            if !self.token.accounts.contains_key(&receiver_id) {
+                storage_cost += registration_cost;
+                assert!(
+                    storage_cost <= attached_deposit,
+                    "Not enough deposit to register {}",
+                    receiver_id
+                );
+                self.token.internal_register_account(&receiver_id);
+            }
+            self.internal_update_snapshots(&[&sender_id, &receiver_id]);
+            self.token
+                .internal_transfer(&sender_id, &receiver_id, amount.into(), memo.clone());
+        }
+        let refund = attached_deposit - storage_cost;
+        if refund > 0 {
+            Promise::new(sender_id).transfer(refund);
+        }
+    }
+}
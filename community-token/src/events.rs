@@ -0,0 +1,44 @@
+use crate::*;
+
+// NEP-297 event logs. Mint and burn are NEP-141 events, metadata updates aren't part of any
+// standard and are emitted under the contract's own name.
+const FT_STANDARD: &str = "nep141";
+const TOKEN_STANDARD: &str = "connesus-token";
+const EVENT_VERSION: &str = "1.0.0";
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct FtMintOrBurn<'a> {
+    owner_id: &'a str,
+    amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct FtMetadataUpdate<'a> {
+    updated_fields: &'a [&'a str],
+}
+
+fn emit_event<T: Serialize>(standard: &str, event: &str, data: T) {
+    let event = serde_json::json!({
+        "standard": standard,
+        "version": EVENT_VERSION,
+        "event": event,
+        "data": [data],
+    });
+    env::log(format!("EVENT_JSON:{}", event).as_bytes());
+}
+
+pub(crate) fn emit_ft_mint(owner_id: &str, amount: Balance, memo: Option<&str>) {
+    emit_event(FT_STANDARD, "ft_mint", FtMintOrBurn { owner_id, amount: amount.into(), memo });
+}
+
+pub(crate) fn emit_ft_burn(owner_id: &str, amount: Balance, memo: Option<&str>) {
+    emit_event(FT_STANDARD, "ft_burn", FtMintOrBurn { owner_id, amount: amount.into(), memo });
+}
+
+pub(crate) fn emit_ft_metadata_update(updated_fields: &[&str]) {
+    emit_event(TOKEN_STANDARD, "ft_metadata_update", FtMetadataUpdate { updated_fields });
+}
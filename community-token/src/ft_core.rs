@@ -0,0 +1,111 @@
+use crate::*;
+use near_contract_standards::fungible_token::core::FungibleTokenCore;
+use near_contract_standards::fungible_token::resolver::FungibleTokenResolver;
+
+impl Contract {
+    // Transfers are blocked while the token is paused or either side is frozen.
+    pub(crate) fn assert_transfer_allowed(&self, sender_id: &AccountId, receiver_id: &AccountId) {
+        assert!(!self.paused, "Transfers are paused");
+        assert!(
+            !self.frozen_accounts.contains(sender_id),
+            "The account {} is frozen",
+            sender_id
+        );
+        assert!(
+            !self.frozen_accounts.contains(receiver_id),
+            "The account {} is frozen",
+            receiver_id
+        );
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenCore for Contract {
+    #[payable]
+    fn ft_transfer(&mut self, receiver_id: ValidAccountId, amount: U128, memo: Option<String>) {
+        let sender_id = env::predecessor_account_id();
+        self.assert_transfer_allowed(&sender_id, receiver_id.as_ref());
+        self.internal_update_snapshots(&[&sender_id, receiver_id.as_ref()]);
+        self.token.ft_transfer(receiver_id, amount, memo)
+    }
+
+    #[payable]
+    fn ft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let sender_id = env::predecessor_account_id();
+        self.assert_transfer_allowed(&sender_id, receiver_id.as_ref());
+        self.internal_update_snapshots(&[&sender_id, receiver_id.as_ref()]);
+        self.token.ft_transfer_call(receiver_id, amount, memo, msg)
+    }
+
+    fn ft_total_supply(&self) -> U128 {
+        self.token.ft_total_supply()
+    }
+
+    fn ft_balance_of(&self, account_id: ValidAccountId) -> U128 {
+        self.token.ft_balance_of(account_id)
+    }
+}
+
+// Refunds of `ft_transfer_call` aren't blocked by pausing or freezing, so tokens can't get stuck
+// with the receiver.
+#[near_bindgen]
+impl FungibleTokenResolver for Contract {
+    #[private]
+    fn ft_resolve_transfer(
+        &mut self,
+        sender_id: ValidAccountId,
+        receiver_id: ValidAccountId,
+        amount: U128,
+    ) -> U128 {
+        let sender_id: AccountId = sender_id.into();
+        self.internal_update_snapshots(&[&sender_id, receiver_id.as_ref()]);
+        let (used_amount, _) =
+            self.token
+                .internal_ft_resolve_transfer(&sender_id, receiver_id, amount);
+        used_amount.into()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn pause(&mut self) {
+        self.assert_owner();
+        self.paused = true;
+    }
+
+    pub fn unpause(&mut self) {
+        self.assert_owner();
+        self.paused = false;
+    }
+
+    pub fn freeze_account(&mut self, account_id: ValidAccountId) {
+        self.assert_owner();
+        self.frozen_accounts.insert(account_id.as_ref());
+    }
+
+    pub fn unfreeze_account(&mut self, account_id: ValidAccountId) {
+        self.assert_owner();
+        self.frozen_accounts.remove(account_id.as_ref());
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn is_frozen(&self, account_id: ValidAccountId) -> bool {
+        self.frozen_accounts.contains(account_id.as_ref())
+    }
+
+    pub fn get_frozen_accounts(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+        let accounts = self.frozen_accounts.as_vector();
+        (from_index..std::cmp::min(from_index + limit, accounts.len()))
+            .filter_map(|index| accounts.get(index))
+            .collect()
+    }
+}
@@ -3,9 +3,37 @@ use near_contract_standards::fungible_token::metadata::{
 };
 use near_contract_standards::fungible_token::FungibleToken;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::LazyOption;
-use near_sdk::json_types::{ValidAccountId, U128};
-use near_sdk::{env, BorshStorageKey, near_bindgen, AccountId, PanicOnDefault, PromiseOrValue};
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet, UnorderedSet};
+use near_sdk::json_types::{ValidAccountId, U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json;
+use near_sdk::{
+    assert_one_yocto, env, BorshStorageKey, near_bindgen, AccountId, Balance, PanicOnDefault,
+    Promise, PromiseOrValue, StorageUsage,
+};
+
+pub use crate::migrations::*;
+
+mod airdrop;
+mod allowance;
+mod batch;
+mod events;
+mod ft_core;
+mod metadata;
+mod migrations;
+mod mint;
+mod permit;
+mod snapshot;
+mod storage;
+mod supply;
+mod vesting;
+
+pub use crate::airdrop::{Airdrop, AirdropOutput};
+pub use crate::batch::MAX_BATCH_TRANSFER_SIZE;
+pub use crate::permit::{PermitAction, PermitKey, PermitKeyOutput, PermitMessage};
+pub use crate::snapshot::{SnapshotId, SNAPSHOT_HISTORY};
+pub use crate::supply::EmissionSchedule;
+pub use crate::vesting::{VestingGrant, VestingOutput};
 
 near_sdk::setup_alloc!();
 
@@ -14,12 +42,58 @@ near_sdk::setup_alloc!();
 pub struct Contract {
     token: FungibleToken,
     metadata: LazyOption<FungibleTokenMetadata>,
+    // Account managing the token, e.g. its minters.
+    owner_id: AccountId,
+    // Accounts allowed to mint new tokens.
+    minters: UnorderedSet<AccountId>,
+    // Total supply can never exceed it. Uncapped if not set.
+    max_supply: Option<Balance>,
+    // Limits how much minters can mint over time.
+    emission_schedule: Option<EmissionSchedule>,
+    // Amount minted by minters so far, counted against the emission schedule.
+    emitted: Balance,
+    // Vesting grant of each account. Granted tokens are held by the token account until claimed.
+    vestings: LookupMap<AccountId, VestingGrant>,
+    // Blocks all transfers while set.
+    paused: bool,
+    // Accounts that can't send or receive transfers.
+    frozen_accounts: UnorderedSet<AccountId>,
+    // Accounts allowed to take snapshots besides the owner.
+    snapshotters: UnorderedSet<AccountId>,
+    // Id of the latest snapshot, snapshot ids start from 1.
+    last_snapshot_id: SnapshotId,
+    // Total supply at the latest snapshots, by snapshot id modulo `SNAPSHOT_HISTORY`.
+    snapshot_total_supplies: LookupMap<u64, Balance>,
+    // Balances recorded lazily on the first change after a snapshot, with their snapshot id,
+    // by account and snapshot id modulo `SNAPSHOT_HISTORY`.
+    account_snapshots: LookupMap<(AccountId, u64), (SnapshotId, Balance)>,
+    // Account proposed by the owner to take over ownership, see `propose_owner`.
+    proposed_owner_id: Option<AccountId>,
+    // Active airdrop, see `create_airdrop`.
+    airdrop: Option<Airdrop>,
+    // Accounts that claimed an airdrop, by the airdrop root.
+    airdrop_claims: LookupSet<(Vec<u8>, AccountId)>,
+    // Amount each spender can transfer from an owner's account, by (owner, spender).
+    allowances: LookupMap<(AccountId, AccountId), Balance>,
+    // Keys accounts sign permits with, see `permit`.
+    permit_keys: LookupMap<AccountId, PermitKey>,
+    // Version of the state layout, see `migrations`.
+    state_version: u32,
 }
 
 #[derive(BorshSerialize, BorshStorageKey)]
 enum StorageKey {
     Token,
     Metadata,
+    Minters,
+    Vestings,
+    FrozenAccounts,
+    Snapshotters,
+    SnapshotTotalSupplies,
+    AccountSnapshots,
+    AirdropClaims,
+    Allowances,
+    PermitKeys,
 }
 
 #[near_bindgen]
@@ -29,20 +103,118 @@ impl Contract {
         owner_id: ValidAccountId,
         total_supply: U128,
         metadata: FungibleTokenMetadata,
+        max_supply: Option<U128>,
+        emission_schedule: Option<EmissionSchedule>,
     ) -> Self {
         metadata.assert_valid();
+        if let Some(emission_schedule) = emission_schedule.as_ref() {
+            emission_schedule.assert_valid();
+        }
         let mut this = Self {
             token: FungibleToken::new(StorageKey::Token),
             metadata: LazyOption::new(StorageKey::Metadata, Some(&metadata)),
+            owner_id: owner_id.clone().into(),
+            minters: UnorderedSet::new(StorageKey::Minters),
+            max_supply: max_supply.map(|max_supply| max_supply.into()),
+            emission_schedule,
+            emitted: 0,
+            vestings: LookupMap::new(StorageKey::Vestings),
+            paused: false,
+            frozen_accounts: UnorderedSet::new(StorageKey::FrozenAccounts),
+            snapshotters: UnorderedSet::new(StorageKey::Snapshotters),
+            last_snapshot_id: 0,
+            snapshot_total_supplies: LookupMap::new(StorageKey::SnapshotTotalSupplies),
+            account_snapshots: LookupMap::new(StorageKey::AccountSnapshots),
+            proposed_owner_id: None,
+            airdrop: None,
+            airdrop_claims: LookupSet::new(StorageKey::AirdropClaims),
+            allowances: LookupMap::new(StorageKey::Allowances),
+            permit_keys: LookupMap::new(StorageKey::PermitKeys),
+            state_version: STATE_VERSION,
         };
+        this.internal_reserve_snapshot_storage();
         this.token.internal_register_account(owner_id.as_ref());
-        this.token.internal_deposit(owner_id.as_ref(), total_supply.into());
+        if total_supply.0 > 0 {
+            this.internal_mint(owner_id.as_ref(), total_supply.into(), Some("Initial supply"));
+        }
         this
     }
+
+    // Upgrades state written by any previous version of the contract to the current layout.
+    // `owner_id` becomes the owner of tokens deployed before the owner was stored,
+    // state that already has an owner keeps it.
+    #[init(ignore_state)]
+    pub fn migrate(owner_id: Option<ValidAccountId>) -> Self {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the token account can migrate"
+        );
+        let state = env::storage_read(b"STATE").expect("Contract is not initialized");
+        migrate_state(&state, owner_id.map(|owner_id| owner_id.into()))
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    // First step of the ownership transfer, the proposed account takes over with `accept_owner`.
+    // Proposing `None` cancels a pending proposal.
+    pub fn propose_owner(&mut self, new_owner_id: Option<ValidAccountId>) {
+        self.assert_owner();
+        self.proposed_owner_id = new_owner_id.map(|account_id| account_id.into());
+    }
+
+    pub fn accept_owner(&mut self) {
+        let predecessor_id = env::predecessor_account_id();
+        assert_eq!(
+            self.proposed_owner_id.as_ref(),
+            Some(&predecessor_id),
+            "Only the proposed owner can accept ownership"
+        );
+        self.owner_id = predecessor_id;
+        self.proposed_owner_id = None;
+    }
+
+    pub fn get_proposed_owner(&self) -> Option<AccountId> {
+        self.proposed_owner_id.clone()
+    }
 }
 
-near_contract_standards::impl_fungible_token_core!(Contract, token);
-near_contract_standards::impl_fungible_token_storage!(Contract, token);
+// Charges storage added since `initial_storage_usage` from the attached deposit and refunds the rest,
+// along with the cost of any storage freed.
+pub(crate) fn refund_deposit(initial_storage_usage: StorageUsage) {
+    refund_deposit_to(initial_storage_usage, &env::predecessor_account_id());
+}
+
+// Same as `refund_deposit`, but the cost of any storage freed goes to `account_id`, who paid for it.
+pub(crate) fn refund_deposit_to(initial_storage_usage: StorageUsage, account_id: &AccountId) {
+    let storage_usage = env::storage_usage();
+    let required_deposit =
+        Balance::from(storage_usage.saturating_sub(initial_storage_usage)) * env::storage_byte_cost();
+    let freed_deposit =
+        Balance::from(initial_storage_usage.saturating_sub(storage_usage)) * env::storage_byte_cost();
+    let attached_deposit = env::attached_deposit();
+    assert!(
+        attached_deposit >= required_deposit,
+        "Must attach {} yoctoNEAR to cover storage",
+        required_deposit
+    );
+    let predecessor_id = env::predecessor_account_id();
+    let refund = attached_deposit - required_deposit;
+    if account_id == &predecessor_id {
+        if refund + freed_deposit > 0 {
+            Promise::new(predecessor_id).transfer(refund + freed_deposit);
+        }
+    } else {
+        if refund > 0 {
+            Promise::new(predecessor_id).transfer(refund);
+        }
+        if freed_deposit > 0 {
+            Promise::new(account_id.clone()).transfer(freed_deposit);
+        }
+    }
+}
 
 #[near_bindgen]
 impl FungibleTokenMetadataProvider for Contract {
@@ -55,8 +227,12 @@ impl FungibleTokenMetadataProvider for Contract {
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use super::*;
+    use std::convert::TryFrom;
+    use near_contract_standards::fungible_token::core::FungibleTokenCore;
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::json_types::{Base58PublicKey, Base64VecU8};
 
-    use near_sdk::test_utils::{VMContextBuilder, accounts};
+    use near_sdk::test_utils::{get_created_receipts, get_logs, VMContextBuilder, accounts};
     use near_sdk::{testing_env, env, Balance};
     use near_sdk::MockedBlockchain;
 
@@ -94,7 +270,7 @@ mod tests {
         // Init contract
         let metadata = get_sample_metadata();
         let total_supply =  U128::from(587000000000000000000000000);
-        let mut contract = Contract::new(accounts(0), total_supply, metadata);
+        let contract = Contract::new(accounts(0), total_supply, metadata, None, None);
 
         testing_env!(
             context.storage_usage(env::storage_usage())
@@ -117,7 +293,7 @@ mod tests {
         testing_env!(context.build());
         let metadata = get_sample_metadata();
         let total_supply = 1_000_000_000_000_000;
-        let mut contract = Contract::new(accounts(0), total_supply.into(), metadata);
+        let mut contract = Contract::new(accounts(0), total_supply.into(), metadata, None, None);
         testing_env!(context
             .storage_usage(env::storage_usage())
             .attached_deposit(contract.storage_balance_bounds().min.into())
@@ -151,4 +327,748 @@ mod tests {
         assert_eq!(contract.ft_balance_of(accounts(0)).0, (total_supply - transfer_amount));
         assert_eq!(contract.ft_balance_of(accounts(1)).0, transfer_amount);
     }
-}
\ No newline at end of file
+
+    fn setup_contract(context: &mut VMContextBuilder) -> Contract {
+        setup_contract_with_supply(context, None, None)
+    }
+
+    fn setup_contract_with_supply(
+        context: &mut VMContextBuilder,
+        max_supply: Option<Balance>,
+        emission_schedule: Option<EmissionSchedule>,
+    ) -> Contract {
+        testing_env!(context.build());
+        let mut contract = Contract::new(
+            accounts(0),
+            TOTAL_SUPPLY.into(),
+            get_sample_metadata(),
+            max_supply.map(|max_supply| max_supply.into()),
+            emission_schedule,
+        );
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(contract.storage_balance_bounds().min.into())
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.storage_deposit(None, None);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(0)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract
+    }
+
+    #[test]
+    fn test_mint_and_burn() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        contract.add_minter(accounts(2));
+        assert!(contract.is_minter(accounts(2)));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.ft_mint(accounts(1), 100.into());
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 100);
+        assert_eq!(contract.ft_total_supply().0, TOTAL_SUPPLY + 100);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build());
+        contract.ft_burn(40.into());
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 60);
+        assert_eq!(contract.ft_total_supply().0, TOTAL_SUPPLY + 60);
+    }
+
+    #[test]
+    fn test_force_unregister_burns_balance() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(1).build());
+        contract.ft_transfer(accounts(1), 100.into(), None);
+        assert_eq!(contract.snapshot().0, 1);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build());
+        assert!(contract.storage_unregister(Some(true)));
+        assert!(contract.storage_balance_of(accounts(1)).is_none());
+        assert_eq!(contract.ft_total_supply().0, TOTAL_SUPPLY - 100);
+        assert_eq!(contract.ft_balance_of_at(accounts(1), 1.into()).0, 100);
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""event":"ft_burn""#));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only minters can mint")]
+    fn test_mint_not_minter() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.ft_mint(accounts(1), 100.into());
+    }
+
+    #[test]
+    fn test_migrate_from_v1() {
+        let context = get_context(false);
+        testing_env!(context.build());
+        let mut token = FungibleToken::new(StorageKey::Token);
+        token.internal_register_account(accounts(1).as_ref());
+        token.internal_deposit(accounts(1).as_ref(), TOTAL_SUPPLY);
+        env::state_write(&OldContractV1 {
+            token,
+            metadata: LazyOption::new(StorageKey::Metadata, Some(&get_sample_metadata())),
+        });
+
+        let contract = Contract::migrate(Some(accounts(1)));
+        assert_eq!(contract.get_owner(), accounts(1).to_string());
+        assert_eq!(contract.ft_total_supply().0, TOTAL_SUPPLY);
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, TOTAL_SUPPLY);
+    }
+
+    #[test]
+    #[should_panic(expected = "Owner is required to migrate version 1 state")]
+    fn test_migrate_from_v1_without_owner() {
+        let context = get_context(false);
+        testing_env!(context.build());
+        env::state_write(&OldContractV1 {
+            token: FungibleToken::new(StorageKey::Token),
+            metadata: LazyOption::new(StorageKey::Metadata, Some(&get_sample_metadata())),
+        });
+        Contract::migrate(None);
+    }
+
+    #[test]
+    fn test_migrate_keeps_owner() {
+        let mut context = get_context(false);
+        let contract = setup_contract(&mut context);
+        env::state_write(&contract);
+
+        let contract = Contract::migrate(Some(accounts(2)));
+        assert_eq!(contract.get_owner(), accounts(0).to_string());
+        assert_eq!(contract.ft_total_supply().0, TOTAL_SUPPLY);
+    }
+
+    #[test]
+    #[should_panic(expected = "Max supply exceeded")]
+    fn test_mint_over_max_supply() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract_with_supply(&mut context, Some(TOTAL_SUPPLY + 100), None);
+        contract.add_minter(accounts(0));
+        assert_eq!(contract.get_mintable_now().0, 100);
+        contract.ft_mint(accounts(1), 101.into());
+    }
+
+    #[test]
+    fn test_emission_schedule() {
+        let mut context = get_context(false);
+        let schedule = EmissionSchedule {
+            start_time: 1000.into(),
+            duration: 1000.into(),
+            amount: 300.into(),
+        };
+        let mut contract = setup_contract_with_supply(&mut context, None, Some(schedule));
+        contract.add_minter(accounts(0));
+        assert_eq!(contract.get_mintable_now().0, 0);
+
+        testing_env!(context.block_timestamp(1500).build());
+        assert_eq!(contract.get_mintable_now().0, 150);
+        contract.ft_mint(accounts(1), 100.into());
+        assert_eq!(contract.get_mintable_now().0, 50);
+
+        testing_env!(context.block_timestamp(5000).build());
+        assert_eq!(contract.get_mintable_now().0, 200);
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount is not unlocked by the emission schedule yet")]
+    fn test_mint_over_emission_schedule() {
+        let mut context = get_context(false);
+        let schedule = EmissionSchedule {
+            start_time: 0.into(),
+            duration: 1000.into(),
+            amount: 300.into(),
+        };
+        let mut contract = setup_contract_with_supply(&mut context, None, Some(schedule));
+        contract.add_minter(accounts(0));
+        testing_env!(context.block_timestamp(500).build());
+        contract.ft_mint(accounts(1), 151.into());
+    }
+
+    #[test]
+    fn test_vesting() {
+        let mut context = get_context(false);
+        context.current_account_id(accounts(4));
+        let mut contract = setup_contract(&mut context);
+        contract.create_vesting(accounts(1), 1000.into(), 100.into(), 50.into(), 200.into(), None);
+        assert_eq!(contract.ft_balance_of(accounts(0)).0, TOTAL_SUPPLY - 1000);
+        assert_eq!(contract.ft_total_supply().0, TOTAL_SUPPLY);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(149).build());
+        assert_eq!(contract.claim_vested().0, 0);
+
+        testing_env!(context.block_timestamp(200).build());
+        assert_eq!(contract.get_vesting(accounts(1)).unwrap().claimable.0, 500);
+        assert_eq!(contract.claim_vested().0, 500);
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 500);
+
+        testing_env!(context.block_timestamp(1000).build());
+        assert_eq!(contract.claim_vested().0, 500);
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 1000);
+        assert!(contract.get_vesting(accounts(1)).is_none());
+    }
+
+    #[test]
+    fn test_revoke_vesting() {
+        let mut context = get_context(false);
+        context.current_account_id(accounts(4));
+        let mut contract = setup_contract(&mut context);
+        contract.create_vesting(accounts(1), 1000.into(), 0.into(), 0.into(), 100.into(), Some(true));
+
+        testing_env!(context.block_timestamp(25).build());
+        assert_eq!(contract.revoke_vesting(accounts(1)).0, 750);
+        assert_eq!(contract.ft_balance_of(accounts(0)).0, TOTAL_SUPPLY - 250);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(100).build());
+        let vesting = contract.get_vesting(accounts(1)).unwrap();
+        assert!(vesting.revoked);
+        assert_eq!(vesting.claimable.0, 250);
+        assert_eq!(contract.claim_vested().0, 250);
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 250);
+    }
+
+    #[test]
+    #[should_panic(expected = "is frozen")]
+    fn test_claim_vested_frozen() {
+        let mut context = get_context(false);
+        context.current_account_id(accounts(4));
+        let mut contract = setup_contract(&mut context);
+        contract.create_vesting(accounts(1), 1000.into(), 0.into(), 0.into(), 100.into(), None);
+        contract.freeze_account(accounts(1));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(100).build());
+        contract.claim_vested();
+    }
+
+    #[test]
+    #[should_panic(expected = "Transfers are paused")]
+    fn test_transfer_paused() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        contract.pause();
+        assert!(contract.is_paused());
+        testing_env!(context.attached_deposit(1).build());
+        contract.ft_transfer(accounts(1), 100.into(), None);
+    }
+
+    #[test]
+    fn test_freeze_account() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        contract.freeze_account(accounts(1));
+        assert!(contract.is_frozen(accounts(1)));
+        assert_eq!(contract.get_frozen_accounts(0, 10), vec![accounts(1).to_string()]);
+        contract.unfreeze_account(accounts(1));
+        assert!(!contract.is_frozen(accounts(1)));
+        testing_env!(context.attached_deposit(1).build());
+        contract.ft_transfer(accounts(1), 100.into(), None);
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "is frozen")]
+    fn test_transfer_to_frozen_account() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        contract.freeze_account(accounts(1));
+        testing_env!(context.attached_deposit(1).build());
+        contract.ft_transfer(accounts(1), 100.into(), None);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(1).build());
+        contract.ft_transfer(accounts(1), 100.into(), None);
+        assert_eq!(contract.snapshot().0, 1);
+        contract.ft_transfer(accounts(1), 50.into(), None);
+        assert_eq!(contract.snapshot().0, 2);
+        assert_eq!(contract.snapshot().0, 3);
+        contract.ft_transfer(accounts(1), 25.into(), None);
+
+        assert_eq!(contract.ft_balance_of_at(accounts(1), 1.into()).0, 100);
+        assert_eq!(contract.ft_balance_of_at(accounts(1), 2.into()).0, 150);
+        assert_eq!(contract.ft_balance_of_at(accounts(1), 3.into()).0, 150);
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 175);
+        assert_eq!(
+            contract.ft_balance_of_at(accounts(0), 1.into()).0,
+            TOTAL_SUPPLY - 100
+        );
+        assert_eq!(contract.ft_total_supply_at(3.into()).0, TOTAL_SUPPLY);
+    }
+
+    #[test]
+    fn test_snapshot_history() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(1).build());
+        contract.ft_transfer(accounts(1), 100.into(), None);
+        contract.snapshot();
+        contract.ft_transfer(accounts(1), 100.into(), None);
+        for _ in 0..SNAPSHOT_HISTORY {
+            contract.snapshot();
+            contract.ft_transfer(accounts(1), 1.into(), None);
+        }
+        // Once the history is full, balance records are overwritten in place.
+        for _ in 0..SNAPSHOT_HISTORY {
+            contract.snapshot();
+            let storage_usage = env::storage_usage();
+            contract.ft_transfer(accounts(1), 1.into(), None);
+            assert_eq!(env::storage_usage(), storage_usage);
+        }
+
+        let last_snapshot_id = contract.get_current_snapshot_id().0;
+        let oldest_snapshot_id = last_snapshot_id + 1 - SNAPSHOT_HISTORY;
+        assert_eq!(
+            contract.ft_balance_of_at(accounts(1), oldest_snapshot_id.into()).0,
+            200 + SNAPSHOT_HISTORY as Balance
+        );
+        assert_eq!(
+            contract.ft_balance_of_at(accounts(1), last_snapshot_id.into()).0,
+            200 + 2 * SNAPSHOT_HISTORY as Balance - 1
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Snapshot expired")]
+    fn test_snapshot_expired() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        for _ in 0..=SNAPSHOT_HISTORY {
+            contract.snapshot();
+        }
+        contract.ft_balance_of_at(accounts(1), 1.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Snapshot expired")]
+    fn test_snapshot_total_supply_expired() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        for _ in 0..=SNAPSHOT_HISTORY {
+            contract.snapshot();
+        }
+        contract.ft_total_supply_at(1.into());
+    }
+
+    #[test]
+    fn test_snapshot_history_prepaid_on_registration() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        contract.add_minter(accounts(0));
+        for _ in 0..SNAPSHOT_HISTORY {
+            contract.snapshot();
+        }
+        let account_id = ValidAccountId::try_from("a".repeat(64)).unwrap();
+        let initial_storage_usage = env::storage_usage();
+        testing_env!(context
+            .storage_usage(initial_storage_usage)
+            .attached_deposit(contract.storage_balance_bounds().min.into())
+            .predecessor_account_id(account_id.clone())
+            .build());
+        contract.storage_deposit(None, None);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(0)
+            .predecessor_account_id(accounts(0))
+            .build());
+        for _ in 0..SNAPSHOT_HISTORY {
+            contract.snapshot();
+            contract.ft_mint(account_id.clone(), 1.into());
+        }
+        assert!(
+            Balance::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost()
+                <= contract.storage_balance_bounds().min.0
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner or snapshotters can take snapshots")]
+    fn test_snapshot_not_authorized() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        contract.add_snapshotter(accounts(2));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        assert_eq!(contract.snapshot().0, 1);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.snapshot();
+    }
+
+    #[test]
+    fn test_set_metadata() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(10_000_000_000_000_000_000_000).build());
+        contract.set_icon(Some("data:image/svg+xml,<svg></svg>".to_string()));
+        contract.set_reference(
+            Some("https://example.com/token.json".to_string()),
+            Some(vec![0; 32].into()),
+        );
+        let mut metadata = contract.ft_metadata();
+        assert_eq!(metadata.icon, Some("data:image/svg+xml,<svg></svg>".to_string()));
+        assert_eq!(metadata.reference, Some("https://example.com/token.json".to_string()));
+        metadata.name = "Community Coin".to_string();
+        contract.set_metadata(metadata);
+        assert_eq!(contract.ft_metadata().name, "Community Coin");
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""updated_fields":["name"]"#));
+    }
+
+    #[test]
+    #[should_panic(expected = "Hash has to be 32 bytes")]
+    fn test_set_reference_invalid_hash() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        contract.set_reference(
+            Some("https://example.com/token.json".to_string()),
+            Some(vec![0; 16].into()),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can call this method")]
+    fn test_set_icon_not_owner() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_icon(None);
+    }
+
+    #[test]
+    fn test_transfer_ownership() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        contract.propose_owner(Some(accounts(2)));
+        assert_eq!(contract.get_proposed_owner(), Some(accounts(2).to_string()));
+        assert_eq!(contract.get_owner(), accounts(0).to_string());
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.accept_owner();
+        assert_eq!(contract.get_owner(), accounts(2).to_string());
+        assert_eq!(contract.get_proposed_owner(), None);
+        contract.pause();
+        assert!(contract.is_paused());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the proposed owner can accept ownership")]
+    fn test_accept_owner_not_proposed() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        contract.propose_owner(Some(accounts(2)));
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.accept_owner();
+    }
+
+    #[test]
+    fn test_batch_transfer() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        // accounts(2) is not registered yet, so the deposit pays for its storage
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        contract.ft_batch_transfer(vec![(accounts(1), 100.into()), (accounts(2), 200.into())], None);
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 100);
+        assert_eq!(contract.ft_balance_of(accounts(2)).0, 200);
+        assert_eq!(contract.ft_balance_of(accounts(0)).0, TOTAL_SUPPLY - 300);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough deposit to register")]
+    fn test_batch_transfer_unregistered() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(1).build());
+        contract.ft_batch_transfer(vec![(accounts(2), 200.into())], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many transfers")]
+    fn test_batch_transfer_too_large() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(1).build());
+        contract.ft_batch_transfer(vec![(accounts(1), 1.into()); MAX_BATCH_TRANSFER_SIZE + 1], None);
+    }
+
+    // Root and proof of the airdrop tree of accounts(1) with 100 and accounts(2) with 200 tokens.
+    fn airdrop_tree() -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let leaf1 = env::sha256(format!("{}:100", accounts(1)).as_bytes());
+        let leaf2 = env::sha256(format!("{}:200", accounts(2)).as_bytes());
+        let root = if leaf1 <= leaf2 {
+            env::sha256(&[leaf1.clone(), leaf2.clone()].concat())
+        } else {
+            env::sha256(&[leaf2.clone(), leaf1.clone()].concat())
+        };
+        (root, leaf1, leaf2)
+    }
+
+    #[test]
+    fn test_airdrop() {
+        let mut context = get_context(false);
+        context.current_account_id(accounts(4));
+        let mut contract = setup_contract(&mut context);
+        let (root, _, leaf2) = airdrop_tree();
+        contract.create_airdrop(root.into(), 300.into());
+        assert_eq!(contract.ft_balance_of(accounts(4)).0, 300);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(MINT_STORAGE_COST)
+            .build());
+        contract.claim_airdrop(vec![leaf2.into()], 100.into());
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 100);
+        assert!(contract.is_airdrop_claimed(accounts(1)));
+        assert_eq!(contract.get_airdrop().unwrap().claimed.0, 100);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        assert_eq!(contract.close_airdrop().0, 200);
+        assert_eq!(contract.ft_balance_of(accounts(0)).0, TOTAL_SUPPLY - 100);
+        assert!(contract.get_airdrop().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover storage")]
+    fn test_airdrop_claim_without_deposit() {
+        let mut context = get_context(false);
+        context.current_account_id(accounts(4));
+        let mut contract = setup_contract(&mut context);
+        let (root, _, leaf2) = airdrop_tree();
+        contract.create_airdrop(root.into(), 300.into());
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.claim_airdrop(vec![leaf2.into()], 100.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Airdrop is already claimed")]
+    fn test_airdrop_claim_twice() {
+        let mut context = get_context(false);
+        context.current_account_id(accounts(4));
+        let mut contract = setup_contract(&mut context);
+        let (root, _, leaf2) = airdrop_tree();
+        contract.create_airdrop(root.into(), 300.into());
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(MINT_STORAGE_COST)
+            .build());
+        contract.claim_airdrop(vec![leaf2.clone().into()], 100.into());
+        contract.claim_airdrop(vec![leaf2.into()], 100.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid proof")]
+    fn test_airdrop_invalid_proof() {
+        let mut context = get_context(false);
+        context.current_account_id(accounts(4));
+        let mut contract = setup_contract(&mut context);
+        let (root, _, leaf2) = airdrop_tree();
+        contract.create_airdrop(root.into(), 300.into());
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(MINT_STORAGE_COST)
+            .build());
+        contract.claim_airdrop(vec![leaf2.into()], 200.into());
+    }
+
+    #[test]
+    fn test_transfer_from() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        contract.ft_approve(accounts(2), 300.into());
+        assert_eq!(contract.ft_allowance(accounts(0), accounts(2)).0, 300);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build());
+        contract.ft_transfer_from(accounts(0), accounts(1), 100.into(), None);
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 100);
+        assert_eq!(contract.ft_allowance(accounts(0), accounts(2)).0, 200);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.ft_approve(accounts(2), 0.into());
+        assert_eq!(contract.ft_allowance(accounts(0), accounts(2)).0, 0);
+    }
+
+    #[test]
+    fn test_transfer_from_spent_allowance_removed() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        contract.ft_approve(accounts(2), 300.into());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build());
+        contract.ft_transfer_from(accounts(0), accounts(1), 300.into(), None);
+        assert!(contract.allowances.get(&(accounts(0).into(), accounts(2).into())).is_none());
+        let transfers = created_transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].0, accounts(0).to_string());
+        assert!(transfers[0].1 > 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Allowance exceeded")]
+    fn test_transfer_from_over_allowance() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        contract.ft_approve(accounts(2), 100.into());
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build());
+        contract.ft_transfer_from(accounts(0), accounts(1), 101.into(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must attach")]
+    fn test_approve_without_storage_deposit() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        testing_env!(context.attached_deposit(1).build());
+        contract.ft_approve(accounts(2), 100.into());
+    }
+
+    fn permit_keypair() -> ed25519_dalek::Keypair {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[7; 32]).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        ed25519_dalek::Keypair { secret, public }
+    }
+
+    // Registers the permit key of accounts(0) and returns a signed transfer of 100 tokens to accounts(1).
+    fn setup_permit(context: &mut VMContextBuilder, contract: &mut Contract) -> (String, Base64VecU8) {
+        let keypair = permit_keypair();
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        contract.set_permit_key(Some(Base58PublicKey([vec![0], keypair.public.to_bytes().to_vec()].concat())));
+        sign_permit(serde_json::json!({ "type": "transfer", "receiver_id": accounts(1), "amount": "100" }))
+    }
+
+    // Returns the first permit of accounts(0) with given action, signed with its permit key.
+    fn sign_permit(action: serde_json::Value) -> (String, Base64VecU8) {
+        use ed25519_dalek::Signer;
+        let message = serde_json::json!({
+            "contract_id": accounts(0),
+            "owner_id": accounts(0),
+            "nonce": "0",
+            "expires_at": "1000",
+            "action": action,
+        })
+        .to_string();
+        let signature = permit_keypair().sign(message.as_bytes()).to_bytes().to_vec();
+        (message, signature.into())
+    }
+
+    // Returns (receiver, amount) of transfers created by the last call.
+    fn created_transfers() -> Vec<(String, Balance)> {
+        get_created_receipts()
+            .iter()
+            .filter_map(|receipt| {
+                // Deposits can exceed u64, so they are read back as floats.
+                let receipt: serde_json::Value =
+                    serde_json::from_str(&serde_json::to_string(receipt).unwrap()).unwrap();
+                let deposit = receipt["actions"][0]["Transfer"]["deposit"].as_f64()?;
+                Some((receipt["receiver_id"].as_str()?.to_string(), deposit as Balance))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_permit() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        let (message, signature) = setup_permit(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(0)
+            .build());
+        contract.permit(message, signature);
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, 100);
+        assert_eq!(contract.get_permit_key(accounts(0)).unwrap().nonce.0, 1);
+    }
+
+    #[test]
+    fn test_permit_refunds_relayer_deposit() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        let (message, signature) = setup_permit(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(10)
+            .build());
+        contract.permit(message, signature);
+        assert_eq!(created_transfers(), vec![(accounts(3).to_string(), 10)]);
+    }
+
+    #[test]
+    fn test_permit_revoke_refunds_owner() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        setup_permit(&mut context, &mut contract);
+        contract.ft_approve(accounts(2), 300.into());
+        let (message, signature) = sign_permit(serde_json::json!({
+            "type": "approve",
+            "spender_id": accounts(2),
+            "amount": "0",
+        }));
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(0)
+            .build());
+        contract.permit(message, signature);
+        assert_eq!(contract.ft_allowance(accounts(0), accounts(2)).0, 0);
+        let transfers = created_transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].0, accounts(0).to_string());
+        assert!(transfers[0].1 > 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid nonce")]
+    fn test_permit_replay() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        let (message, signature) = setup_permit(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.permit(message.clone(), signature.clone());
+        contract.permit(message, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid signature")]
+    fn test_permit_tampered() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        let (message, signature) = setup_permit(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.permit(message.replace("100", "999"), signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Permit has expired")]
+    fn test_permit_expired() {
+        let mut context = get_context(false);
+        let mut contract = setup_contract(&mut context);
+        let (message, signature) = setup_permit(&mut context, &mut contract);
+        testing_env!(context.block_timestamp(1000).build());
+        contract.permit(message, signature);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::*;
+use crate::events::emit_ft_metadata_update;
+use near_sdk::json_types::Base64VecU8;
+
+impl Contract {
+    // Replaces the metadata, paying for extra storage from the attached deposit and
+    // refunding the rest. Decimals can't change as they define what balances mean.
+    fn internal_set_metadata(&mut self, metadata: FungibleTokenMetadata) {
+        metadata.assert_valid();
+        let old = self.metadata.get().unwrap();
+        assert_eq!(metadata.decimals, old.decimals, "Decimals can't be changed");
+        let updated_fields: Vec<&str> = [
+            ("name", old.name != metadata.name),
+            ("symbol", old.symbol != metadata.symbol),
+            ("icon", old.icon != metadata.icon),
+            ("reference", old.reference != metadata.reference),
+            ("reference_hash", old.reference_hash != metadata.reference_hash),
+        ]
+        .iter()
+        .filter(|(_, updated)| *updated)
+        .map(|(field, _)| *field)
+        .collect();
+        let initial_storage_usage = env::storage_usage();
+        self.metadata.set(&metadata);
+        refund_deposit(initial_storage_usage);
+        if !updated_fields.is_empty() {
+            emit_ft_metadata_update(&updated_fields);
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn set_metadata(&mut self, metadata: FungibleTokenMetadata) {
+        self.assert_owner();
+        self.internal_set_metadata(metadata);
+    }
+
+    #[payable]
+    pub fn set_icon(&mut self, icon: Option<String>) {
+        self.assert_owner();
+        let mut metadata = self.metadata.get().unwrap();
+        metadata.icon = icon;
+        self.internal_set_metadata(metadata);
+    }
+
+    // Reference and its hash are set together, `assert_valid` checks the hash is 32 bytes.
+    #[payable]
+    pub fn set_reference(&mut self, reference: Option<String>, reference_hash: Option<Base64VecU8>) {
+        self.assert_owner();
+        let mut metadata = self.metadata.get().unwrap();
+        metadata.reference = reference;
+        metadata.reference_hash = reference_hash;
+        self.internal_set_metadata(metadata);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::*;
+
+// Version of the state layout written by this code.
+// 1: initial layout, fungible token and metadata only.
+// 2: owner, minters, supply limits, vesting, pausing and freezing, snapshots, airdrops,
+//    allowances and permit keys.
+pub const STATE_VERSION: u32 = 2;
+
+// Contract state layout of version 1.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldContractV1 {
+    pub token: FungibleToken,
+    pub metadata: LazyOption<FungibleTokenMetadata>,
+}
+
+// Reads state written by any previous version of the contract.
+// Version 1 state had no owner, so `owner_id` is required for it and ignored otherwise.
+pub(crate) fn migrate_state(state: &[u8], owner_id: Option<AccountId>) -> Contract {
+    if let Ok(this) = Contract::try_from_slice(state) {
+        assert_eq!(this.state_version, STATE_VERSION, "Unknown state version");
+        return this;
+    }
+    let old = OldContractV1::try_from_slice(state).expect("Unknown state version");
+    let mut this =
+        Contract::from_v1(old, owner_id.expect("Owner is required to migrate version 1 state"));
+    // Accounts registered from now on prepay their snapshot history.
+    this.internal_reserve_snapshot_storage();
+    this
+}
+
+impl Contract {
+    // Existing tokens stay uncapped until the owner sets max supply.
+    fn from_v1(old: OldContractV1, owner_id: AccountId) -> Self {
+        Self {
+            token: old.token,
+            metadata: old.metadata,
+            owner_id,
+            minters: UnorderedSet::new(StorageKey::Minters),
+            max_supply: None,
+            emission_schedule: None,
+            emitted: 0,
+            vestings: LookupMap::new(StorageKey::Vestings),
+            paused: false,
+            frozen_accounts: UnorderedSet::new(StorageKey::FrozenAccounts),
+            snapshotters: UnorderedSet::new(StorageKey::Snapshotters),
+            last_snapshot_id: 0,
+            snapshot_total_supplies: LookupMap::new(StorageKey::SnapshotTotalSupplies),
+            account_snapshots: LookupMap::new(StorageKey::AccountSnapshots),
+            proposed_owner_id: None,
+            airdrop: None,
+            airdrop_claims: LookupSet::new(StorageKey::AirdropClaims),
+            allowances: LookupMap::new(StorageKey::Allowances),
+            permit_keys: LookupMap::new(StorageKey::PermitKeys),
+            state_version: STATE_VERSION,
+        }
+    }
+}
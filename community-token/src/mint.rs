@@ -0,0 +1,79 @@
+use crate::*;
+use crate::events::{emit_ft_burn, emit_ft_mint};
+
+impl Contract {
+    pub(crate) fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only owner can call this method"
+        );
+    }
+
+    // Deposits new tokens to a registered account. Total supply is increased by `internal_deposit`.
+    pub(crate) fn internal_mint(&mut self, account_id: &AccountId, amount: Balance, memo: Option<&str>) {
+        assert!(amount > 0, "The amount should be a positive number");
+        if let Some(max_supply) = self.max_supply {
+            assert!(
+                self.token.total_supply.saturating_add(amount) <= max_supply,
+                "Max supply exceeded"
+            );
+        }
+        self.internal_update_snapshots(&[account_id]);
+        self.token.internal_deposit(account_id, amount);
+        emit_ft_mint(account_id, amount, memo);
+    }
+
+    // Withdraws tokens from the account. Total supply is decreased by `internal_withdraw`.
+    pub(crate) fn internal_burn(&mut self, account_id: &AccountId, amount: Balance, memo: Option<&str>) {
+        assert!(amount > 0, "The amount should be a positive number");
+        self.internal_update_snapshots(&[account_id]);
+        self.token.internal_withdraw(account_id, amount);
+        emit_ft_burn(account_id, amount, memo);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn add_minter(&mut self, account_id: ValidAccountId) {
+        self.assert_owner();
+        self.minters.insert(account_id.as_ref());
+    }
+
+    pub fn remove_minter(&mut self, account_id: ValidAccountId) {
+        self.assert_owner();
+        self.minters.remove(account_id.as_ref());
+    }
+
+    // Mints new tokens to the account, which must be registered. Only minters can mint,
+    // up to the amount unlocked by the emission schedule.
+    pub fn ft_mint(&mut self, account_id: ValidAccountId, amount: U128) {
+        assert!(
+            self.minters.contains(&env::predecessor_account_id()),
+            "Only minters can mint"
+        );
+        if let Some(emission_schedule) = self.emission_schedule.as_ref() {
+            assert!(
+                self.emitted + amount.0 <= emission_schedule.unlocked_amount(env::block_timestamp()),
+                "Amount is not unlocked by the emission schedule yet"
+            );
+        }
+        self.emitted += amount.0;
+        self.internal_mint(account_id.as_ref(), amount.into(), None);
+    }
+
+    // Burns tokens of the caller.
+    #[payable]
+    pub fn ft_burn(&mut self, amount: U128) {
+        assert_one_yocto();
+        self.internal_burn(&env::predecessor_account_id(), amount.into(), None);
+    }
+
+    pub fn get_minters(&self) -> Vec<AccountId> {
+        self.minters.to_vec()
+    }
+
+    pub fn is_minter(&self, account_id: ValidAccountId) -> bool {
+        self.minters.contains(account_id.as_ref())
+    }
+}
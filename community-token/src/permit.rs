@@ -0,0 +1,140 @@
+use crate::*;
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use near_sdk::json_types::{Base58PublicKey, Base64VecU8};
+use std::convert::TryFrom;
+
+// Key an account signs permits with, along with the nonce its next permit must use.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PermitKey {
+    pub public_key: Vec<u8>,
+    pub nonce: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PermitAction {
+    Transfer {
+        receiver_id: ValidAccountId,
+        amount: U128,
+        memo: Option<String>,
+    },
+    Approve {
+        spender_id: ValidAccountId,
+        amount: U128,
+    },
+}
+
+// Message signed by the owner of the tokens. `contract_id` is the token account, so permits
+// can't be replayed on other tokens. `expires_at` is a timestamp in nanoseconds.
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PermitMessage {
+    pub contract_id: ValidAccountId,
+    pub owner_id: ValidAccountId,
+    pub nonce: U64,
+    pub expires_at: U64,
+    pub action: PermitAction,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PermitKeyOutput {
+    pub public_key: Base58PublicKey,
+    pub nonce: U64,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Sets the ed25519 key the caller signs permits with, `None` removes it. The nonce is kept
+    // when the key changes, so permits already used can't be replayed. Storage of a new key is paid
+    // from the attached deposit. Requires at least 1 yoctoNEAR.
+    #[payable]
+    pub fn set_permit_key(&mut self, public_key: Option<Base58PublicKey>) {
+        assert!(
+            env::attached_deposit() >= 1,
+            "Requires attached deposit of at least 1 yoctoNEAR"
+        );
+        let account_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        match public_key {
+            Some(public_key) => {
+                let public_key: Vec<u8> = public_key.into();
+                assert!(
+                    public_key.len() == 33 && public_key[0] == 0,
+                    "Only ed25519 keys are supported"
+                );
+                let nonce = self.permit_keys.get(&account_id).map_or(0, |key| key.nonce);
+                self.permit_keys.insert(&account_id, &PermitKey {
+                    public_key: public_key[1..].to_vec(),
+                    nonce,
+                });
+            }
+            None => {
+                let mut key = self.permit_keys.get(&account_id).expect("No permit key");
+                // Keeps the nonce, so only the key is removed.
+                key.public_key = vec![];
+                self.permit_keys.insert(&account_id, &key);
+            }
+        }
+        refund_deposit(initial_storage_usage);
+    }
+
+    // Executes a transfer or an approval signed by the owner of the tokens, submitted by a relayer
+    // paying for gas. `message` is the JSON of `PermitMessage` exactly as signed. Storage of a new
+    // allowance is paid from the attached deposit, the rest goes back to the relayer. Storage freed
+    // by removing an allowance is refunded to the owner, who paid for it.
+    #[payable]
+    pub fn permit(&mut self, message: String, signature: Base64VecU8) {
+        let permit: PermitMessage = serde_json::from_str(&message).expect("Invalid permit message");
+        let owner_id: AccountId = permit.owner_id.into();
+        assert_eq!(
+            permit.contract_id.as_ref(),
+            &env::current_account_id(),
+            "Permit is for another contract"
+        );
+        assert!(
+            env::block_timestamp() < permit.expires_at.0,
+            "Permit has expired"
+        );
+        let mut key = self.permit_keys.get(&owner_id).expect("No permit key");
+        assert_eq!(permit.nonce.0, key.nonce, "Invalid nonce");
+        let public_key = PublicKey::from_bytes(&key.public_key).expect("No permit key");
+        let signature = Signature::try_from(signature.0.as_slice()).expect("Invalid signature");
+        assert!(
+            public_key.verify(message.as_bytes(), &signature).is_ok(),
+            "Invalid signature"
+        );
+        key.nonce += 1;
+        self.permit_keys.insert(&owner_id, &key);
+
+        match permit.action {
+            PermitAction::Transfer { receiver_id, amount, memo } => {
+                let receiver_id: AccountId = receiver_id.into();
+                self.assert_transfer_allowed(&owner_id, &receiver_id);
+                self.internal_update_snapshots(&[&owner_id, &receiver_id]);
+                self.token
+                    .internal_transfer(&owner_id, &receiver_id, amount.into(), memo);
+                let deposit = env::attached_deposit();
+                if deposit > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(deposit);
+                }
+            }
+            PermitAction::Approve { spender_id, amount } => {
+                let initial_storage_usage = env::storage_usage();
+                self.internal_approve(owner_id.clone(), spender_id.into(), amount.into());
+                refund_deposit_to(initial_storage_usage, &owner_id);
+            }
+        }
+    }
+
+    pub fn get_permit_key(&self, account_id: ValidAccountId) -> Option<PermitKeyOutput> {
+        self.permit_keys
+            .get(account_id.as_ref())
+            .filter(|key| !key.public_key.is_empty())
+            .map(|key| PermitKeyOutput {
+                public_key: Base58PublicKey([vec![0], key.public_key].concat()),
+                nonce: key.nonce.into(),
+            })
+    }
+}
@@ -0,0 +1,106 @@
+use crate::*;
+
+pub type SnapshotId = u64;
+
+// Number of latest snapshots balances and total supply can be queried at. Older snapshots expire,
+// so each account keeps at most this many balance records, overwritten in place.
+pub const SNAPSHOT_HISTORY: u64 = 32;
+
+impl Contract {
+    // Adds storage of the balance records an account can have to the registration cost,
+    // so every registered account prepays its snapshot history.
+    pub(crate) fn internal_reserve_snapshot_storage(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_key = ("a".repeat(64), 0);
+        self.account_snapshots.insert(&tmp_key, &(0, 0));
+        let record_storage_usage = env::storage_usage() - initial_storage_usage;
+        self.account_snapshots.remove(&tmp_key);
+        self.token.account_storage_usage += record_storage_usage * SNAPSHOT_HISTORY;
+    }
+
+    fn assert_snapshot_available(&self, snapshot_id: SnapshotId) {
+        assert!(
+            snapshot_id > 0 && snapshot_id <= self.last_snapshot_id,
+            "Snapshot not found"
+        );
+        assert!(snapshot_id + SNAPSHOT_HISTORY > self.last_snapshot_id, "Snapshot expired");
+    }
+
+    // Records balances of the accounts taken at the latest snapshot, before they change.
+    // Balance at an older snapshot is the one recorded for the first snapshot after it,
+    // or the current balance if nothing was recorded since.
+    pub(crate) fn internal_update_snapshots(&mut self, account_ids: &[&AccountId]) {
+        let snapshot_id = self.last_snapshot_id;
+        if snapshot_id == 0 {
+            return;
+        }
+        for account_id in account_ids {
+            let key = ((*account_id).clone(), snapshot_id % SNAPSHOT_HISTORY);
+            if self.account_snapshots.get(&key).map(|(id, _)| id) != Some(snapshot_id) {
+                let balance = self.token.accounts.get(account_id).unwrap_or(0);
+                self.account_snapshots.insert(&key, &(snapshot_id, balance));
+            }
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn add_snapshotter(&mut self, account_id: ValidAccountId) {
+        self.assert_owner();
+        self.snapshotters.insert(account_id.as_ref());
+    }
+
+    pub fn remove_snapshotter(&mut self, account_id: ValidAccountId) {
+        self.assert_owner();
+        self.snapshotters.remove(account_id.as_ref());
+    }
+
+    // Takes a snapshot of all balances and returns its id. Callable by the owner and snapshotters,
+    // e.g. a DAO taking voting weights at proposal creation. Balances stay queryable until
+    // `SNAPSHOT_HISTORY` newer snapshots are taken.
+    pub fn snapshot(&mut self) -> U64 {
+        let predecessor_id = env::predecessor_account_id();
+        assert!(
+            predecessor_id == self.owner_id || self.snapshotters.contains(&predecessor_id),
+            "Only owner or snapshotters can take snapshots"
+        );
+        self.last_snapshot_id += 1;
+        self.snapshot_total_supplies
+            .insert(&(self.last_snapshot_id % SNAPSHOT_HISTORY), &self.token.total_supply);
+        self.last_snapshot_id.into()
+    }
+
+    pub fn get_snapshotters(&self) -> Vec<AccountId> {
+        self.snapshotters.to_vec()
+    }
+
+    // Id of the latest snapshot, 0 if none was taken yet.
+    pub fn get_current_snapshot_id(&self) -> U64 {
+        self.last_snapshot_id.into()
+    }
+
+    pub fn ft_balance_of_at(&self, account_id: ValidAccountId, snapshot_id: U64) -> U128 {
+        let snapshot_id: SnapshotId = snapshot_id.into();
+        self.assert_snapshot_available(snapshot_id);
+        let account_id: AccountId = account_id.into();
+        for id in snapshot_id..=self.last_snapshot_id {
+            let key = (account_id.clone(), id % SNAPSHOT_HISTORY);
+            if let Some((recorded_id, balance)) = self.account_snapshots.get(&key) {
+                if recorded_id == id {
+                    return balance.into();
+                }
+            }
+        }
+        self.token.accounts.get(&account_id).unwrap_or(0).into()
+    }
+
+    pub fn ft_total_supply_at(&self, snapshot_id: U64) -> U128 {
+        let snapshot_id: SnapshotId = snapshot_id.into();
+        self.assert_snapshot_available(snapshot_id);
+        self.snapshot_total_supplies
+            .get(&(snapshot_id % SNAPSHOT_HISTORY))
+            .unwrap()
+            .into()
+    }
+}
@@ -0,0 +1,78 @@
+use crate::*;
+
+// Linear emission: `amount` is unlocked evenly from `start_time` over `duration` nanoseconds.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EmissionSchedule {
+    pub start_time: U64,
+    pub duration: U64,
+    pub amount: U128,
+}
+
+impl EmissionSchedule {
+    pub fn assert_valid(&self) {
+        assert!(self.duration.0 > 0, "Emission duration should be positive");
+    }
+
+    pub fn unlocked_amount(&self, timestamp: u64) -> Balance {
+        if timestamp <= self.start_time.0 {
+            return 0;
+        }
+        let elapsed = std::cmp::min(timestamp - self.start_time.0, self.duration.0);
+        // Whole and remainder parts are scaled separately so `amount * elapsed` can't overflow.
+        let amount = self.amount.0;
+        let duration = Balance::from(self.duration.0);
+        let elapsed = Balance::from(elapsed);
+        amount / duration * elapsed + amount % duration * elapsed / duration
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Sets or lowers max supply. It can't go below the current total supply and can't be raised once set.
+    pub fn set_max_supply(&mut self, max_supply: U128) {
+        self.assert_owner();
+        assert!(
+            max_supply.0 >= self.token.total_supply,
+            "Max supply is below total supply"
+        );
+        if let Some(current) = self.max_supply {
+            assert!(max_supply.0 <= current, "Max supply can't be raised");
+        }
+        self.max_supply = Some(max_supply.0);
+    }
+
+    // Sets the emission schedule for minters. It can only be set once.
+    pub fn set_emission_schedule(&mut self, emission_schedule: EmissionSchedule) {
+        self.assert_owner();
+        assert!(self.emission_schedule.is_none(), "Emission schedule is already set");
+        emission_schedule.assert_valid();
+        self.emission_schedule = Some(emission_schedule);
+    }
+
+    pub fn get_max_supply(&self) -> Option<U128> {
+        self.max_supply.map(|max_supply| max_supply.into())
+    }
+
+    pub fn get_emission_schedule(&self) -> Option<EmissionSchedule> {
+        self.emission_schedule.clone()
+    }
+
+    // Amount minters can mint right now, limited by both the emission schedule and max supply.
+    pub fn get_mintable_now(&self) -> U128 {
+        let by_schedule = self
+            .emission_schedule
+            .as_ref()
+            .map(|emission_schedule| {
+                emission_schedule
+                    .unlocked_amount(env::block_timestamp())
+                    .saturating_sub(self.emitted)
+            })
+            .unwrap_or(Balance::MAX);
+        let by_supply = self
+            .max_supply
+            .map(|max_supply| max_supply.saturating_sub(self.token.total_supply))
+            .unwrap_or(Balance::MAX - self.token.total_supply);
+        std::cmp::min(by_schedule, by_supply).into()
+    }
+}
@@ -0,0 +1,157 @@
+use crate::*;
+
+// Tokens granted to an account, vesting linearly from `start` over `duration` with nothing
+// vested before `start + cliff`. Times are in nanoseconds.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct VestingGrant {
+    pub amount: Balance,
+    pub claimed: Balance,
+    pub start: u64,
+    pub cliff: u64,
+    pub duration: u64,
+    pub revocable: bool,
+    // Revoked grants keep only the amount vested at revocation, all of it claimable.
+    pub revoked: bool,
+}
+
+impl VestingGrant {
+    pub fn vested_amount(&self, timestamp: u64) -> Balance {
+        if self.revoked {
+            return self.amount;
+        }
+        if timestamp < self.start + self.cliff {
+            return 0;
+        }
+        let elapsed = std::cmp::min(timestamp - self.start, self.duration);
+        if elapsed == self.duration {
+            return self.amount;
+        }
+        let duration = Balance::from(self.duration);
+        let elapsed = Balance::from(elapsed);
+        self.amount / duration * elapsed + self.amount % duration * elapsed / duration
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VestingOutput {
+    pub amount: U128,
+    pub claimed: U128,
+    pub vested: U128,
+    pub claimable: U128,
+    pub start: U64,
+    pub cliff: U64,
+    pub duration: U64,
+    pub revocable: bool,
+    pub revoked: bool,
+}
+
+impl Contract {
+    // Moves tokens between the escrow, i.e. the token account holding vesting grants and airdrops,
+    // and other accounts. The owner may be the token account itself.
+    pub(crate) fn internal_escrow_transfer(&mut self, sender_id: &AccountId, receiver_id: &AccountId, amount: Balance, memo: &str) {
+        if receiver_id == &env::current_account_id() && !self.token.accounts.contains_key(receiver_id) {
+            self.token.internal_register_account(receiver_id);
+        }
+        if amount > 0 && sender_id != receiver_id {
+            self.internal_update_snapshots(&[sender_id, receiver_id]);
+            self.token
+                .internal_transfer(sender_id, receiver_id, amount, Some(memo.to_string()));
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Grants `amount` of the owner's tokens to the account. The account must be registered and
+    // can't have another grant still vesting or unclaimed.
+    pub fn create_vesting(
+        &mut self,
+        account_id: ValidAccountId,
+        amount: U128,
+        start: U64,
+        cliff: U64,
+        duration: U64,
+        revocable: Option<bool>,
+    ) {
+        self.assert_owner();
+        let account_id: AccountId = account_id.into();
+        assert!(amount.0 > 0, "The amount should be a positive number");
+        assert!(duration.0 > 0, "Vesting duration should be positive");
+        assert!(cliff.0 <= duration.0, "Cliff can't be longer than duration");
+        assert!(
+            self.token.accounts.contains_key(&account_id),
+            "The account {} is not registered", account_id
+        );
+        if let Some(grant) = self.vestings.get(&account_id) {
+            assert_eq!(grant.claimed, grant.amount, "The account already has a vesting grant");
+        }
+        let escrow_id = env::current_account_id();
+        let owner_id = self.owner_id.clone();
+        self.internal_escrow_transfer(&owner_id, &escrow_id, amount.0, "Vesting grant");
+        self.vestings.insert(&account_id, &VestingGrant {
+            amount: amount.0,
+            claimed: 0,
+            start: start.0,
+            cliff: cliff.0,
+            duration: duration.0,
+            revocable: revocable.unwrap_or(false),
+            revoked: false,
+        });
+    }
+
+    // Transfers all vested and not yet claimed tokens to the caller.
+    pub fn claim_vested(&mut self) -> U128 {
+        let account_id = env::predecessor_account_id();
+        self.assert_transfer_allowed(&env::current_account_id(), &account_id);
+        let mut grant = self.vestings.get(&account_id).expect("No vesting grant");
+        let claimable = grant.vested_amount(env::block_timestamp()) - grant.claimed;
+        grant.claimed += claimable;
+        if grant.claimed == grant.amount {
+            self.vestings.remove(&account_id);
+        } else {
+            self.vestings.insert(&account_id, &grant);
+        }
+        self.internal_escrow_transfer(&env::current_account_id(), &account_id, claimable, "Vesting claim");
+        claimable.into()
+    }
+
+    // Stops a revocable grant and returns its unvested tokens to the owner.
+    // Tokens vested so far stay claimable by the account.
+    pub fn revoke_vesting(&mut self, account_id: ValidAccountId) -> U128 {
+        self.assert_owner();
+        let account_id: AccountId = account_id.into();
+        let mut grant = self.vestings.get(&account_id).expect("No vesting grant");
+        assert!(grant.revocable, "Vesting grant is not revocable");
+        assert!(!grant.revoked, "Vesting grant is already revoked");
+        let vested = grant.vested_amount(env::block_timestamp());
+        let unvested = grant.amount - vested;
+        grant.amount = vested;
+        grant.revoked = true;
+        if grant.claimed == grant.amount {
+            self.vestings.remove(&account_id);
+        } else {
+            self.vestings.insert(&account_id, &grant);
+        }
+        let owner_id = self.owner_id.clone();
+        self.internal_escrow_transfer(&env::current_account_id(), &owner_id, unvested, "Vesting revoked");
+        unvested.into()
+    }
+
+    pub fn get_vesting(&self, account_id: ValidAccountId) -> Option<VestingOutput> {
+        self.vestings.get(account_id.as_ref()).map(|grant| {
+            let vested = grant.vested_amount(env::block_timestamp());
+            VestingOutput {
+                amount: grant.amount.into(),
+                claimed: grant.claimed.into(),
+                vested: vested.into(),
+                claimable: (vested - grant.claimed).into(),
+                start: grant.start.into(),
+                cliff: grant.cliff.into(),
+                duration: grant.duration.into(),
+                revocable: grant.revocable,
+                revoked: grant.revoked,
+            }
+        })
+    }
+}
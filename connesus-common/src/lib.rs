@@ -0,0 +1,47 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base58CryptoHash, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{AccountId, Balance};
+use std::collections::HashMap;
+
+pub use crate::metadata::*;
+
+mod metadata;
+
+// Arguments of the DAO `new` method, stored by the factory for every DAO it created.
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DaoArgs {
+    pub token_contract_id: AccountId,
+    pub metadata: DaoMetadata,
+    pub owner_id: AccountId,
+    // Hash of the code deployed to the DAO, set by the factory. Unknown for DAOs created before versions were tracked.
+    #[serde(default)]
+    pub code_hash: Option<Base58CryptoHash>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BountyInput {
+    pub description: String,
+    pub token: AccountId,
+    pub start_time: U64,
+    pub duration: U64,
+    pub claimer: HashMap<AccountId, Balance>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum TransferPurpose {
+    Delegate(AccountId),
+    OpenDonate,
+    ProposalDonate(u64),
+    CreateBounty(BountyInput),
+}
+
+// `msg` of `ft_transfer_call` to a DAO.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TransferArgs {
+    pub purpose: TransferPurpose, // 1 for delegate, 2 for open donate, 3 for proposal donate,
+}
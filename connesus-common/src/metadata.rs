@@ -0,0 +1,161 @@
+use crate::*;
+
+pub const MAX_NAME_LENGTH: usize = 64;
+pub const MAX_PURPOSE_LENGTH: usize = 1024;
+pub const MAX_URL_LENGTH: usize = 256;
+// Symbol is used as the DAO account name, so it's kept short and alphanumeric.
+pub const MIN_SYMBOL_LENGTH: usize = 2;
+pub const MAX_SYMBOL_LENGTH: usize = 16;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DaoMetadata {
+    // Name of the DAO.
+    pub name: String,
+
+    // Purpose of this DAO.
+    pub purpose: String,
+    // Generic metadata. Can be used by specific UI to store additional data.
+    // This is not used by anything in the contract.
+    pub thumbnail: String,
+
+    pub symbol: String,
+
+    pub facebook: Option<String>,
+
+    pub twitter: Option<String>,
+
+    pub discord: Option<String>,
+
+    pub instagram: Option<String>,
+
+    #[serde(default)]
+    pub youtube: Option<String>,
+}
+
+impl DaoMetadata {
+    pub fn assert_valid(&self) {
+        assert!(
+            !self.name.is_empty() && self.name.len() <= MAX_NAME_LENGTH,
+            "ERR_INVALID_NAME"
+        );
+        assert!(self.purpose.len() <= MAX_PURPOSE_LENGTH, "ERR_INVALID_PURPOSE");
+        assert!(is_valid_symbol(&self.symbol), "ERR_INVALID_SYMBOL");
+        assert!(
+            self.thumbnail.is_empty() || is_valid_url(&self.thumbnail),
+            "ERR_INVALID_THUMBNAIL"
+        );
+        for url in [
+            &self.facebook,
+            &self.twitter,
+            &self.discord,
+            &self.instagram,
+            &self.youtube,
+        ]
+        .iter()
+        .filter_map(|url| url.as_ref())
+        {
+            assert!(is_valid_url(url), "ERR_INVALID_URL");
+        }
+    }
+}
+
+pub fn is_valid_symbol(symbol: &str) -> bool {
+    (MIN_SYMBOL_LENGTH..=MAX_SYMBOL_LENGTH).contains(&symbol.len())
+        && symbol.bytes().all(|c| c.is_ascii_alphanumeric())
+}
+
+// Accepts http(s) URLs with a dotted host and no whitespace.
+pub fn is_valid_url(url: &str) -> bool {
+    if url.len() > MAX_URL_LENGTH || url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return false;
+    }
+    let rest = match url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    host.contains('.') && !host.starts_with('.') && !host.ends_with('.')
+}
+
+// `DaoMetadata` layout before the youtube link was added.
+// Kept to read state written by previous versions of the contracts.
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct OldDaoMetadata {
+    pub name: String,
+    pub purpose: String,
+    pub thumbnail: String,
+    pub symbol: String,
+    pub facebook: Option<String>,
+    pub twitter: Option<String>,
+    pub discord: Option<String>,
+    pub instagram: Option<String>,
+}
+
+impl From<OldDaoMetadata> for DaoMetadata {
+    fn from(old: OldDaoMetadata) -> Self {
+        Self {
+            name: old.name,
+            purpose: old.purpose,
+            thumbnail: old.thumbnail,
+            symbol: old.symbol,
+            facebook: old.facebook,
+            twitter: old.twitter,
+            discord: old.discord,
+            instagram: old.instagram,
+            youtube: None,
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    fn metadata() -> DaoMetadata {
+        DaoMetadata {
+            name: "CZ Binance".to_string(),
+            purpose: "Build the future with blockchain".to_string(),
+            thumbnail: "https://pbs.twimg.com/profile_images/1470780411747844096/vpxt_095_400x400.jpg".to_string(),
+            symbol: "CZB".to_string(),
+            facebook: Some("https://facebook.com".to_string()),
+            twitter: None,
+            discord: None,
+            instagram: None,
+            youtube: Some("https://youtube.com/c/test?x=1".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_valid_metadata() {
+        metadata().assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_SYMBOL")]
+    fn test_invalid_symbol() {
+        let mut metadata = metadata();
+        metadata.symbol = "cz.b".to_string();
+        metadata.assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_URL")]
+    fn test_invalid_url() {
+        let mut metadata = metadata();
+        metadata.discord = Some("discord.com".to_string());
+        metadata.assert_valid();
+    }
+
+    #[test]
+    fn test_is_valid_url() {
+        assert!(is_valid_url("http://example.com/path"));
+        assert!(!is_valid_url("https://"));
+        assert!(!is_valid_url("https://localhost"));
+        assert!(!is_valid_url("https://example .com"));
+        assert!(!is_valid_url("ftp://example.com"));
+    }
+}
@@ -1,8 +1,8 @@
 use crate::*;
 
 // Bounty information.
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
-#[serde(crate = "near_sdk::serde")]
+// Claimers live in their own collection under a per-bounty storage prefix.
+#[derive(BorshSerialize, BorshDeserialize)]
 pub struct Bounty {
     pub description: String,
     pub token: OldAccountId,
@@ -10,40 +10,66 @@ pub struct Bounty {
     pub rest: Balance,
     pub start_time: U64,
     pub duration: U64,
-    pub claimer: HashMap<AccountId, Balance>,
+    pub claimer: LookupMap<AccountId, Balance>,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
-#[serde(crate = "near_sdk::serde")]
-pub enum VersionedBounty {
-    Default(Bounty),
+// Bounty layout of state version 1, with claimers kept inline.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct BountyV1 {
+    pub description: String,
+    pub token: OldAccountId,
+    pub total: Balance,
+    pub rest: Balance,
+    pub start_time: U64,
+    pub duration: U64,
+    pub claimer: HashMap<AccountId, Balance>,
 }
 
-impl From<VersionedBounty> for Bounty {
-    fn from(v: VersionedBounty) -> Self {
-        match v {
-            VersionedBounty::Default(b) => b,
+impl BountyV1 {
+    // Moves inline claimers into a per-bounty collection.
+    pub fn upgrade(self, id: u64) -> Bounty {
+        let mut claimer = LookupMap::new(StorageKeys::BountyClaimers { bounty_id: id });
+        for (account_id, amount) in self.claimer.iter() {
+            claimer.insert(account_id, amount);
+        }
+        Bounty {
+            description: self.description,
+            token: self.token,
+            total: self.total,
+            rest: self.rest,
+            start_time: self.start_time,
+            duration: self.duration,
+            claimer,
         }
     }
 }
 
+// Bounties stored in state version 1 stay `Default` until they are modified,
+// at which point they are upgraded and written back as `V2`.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub enum VersionedBounty {
+    Default(BountyV1),
+    V2(Bounty),
+}
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
-#[serde(crate = "near_sdk::serde")]
-pub struct BountyInput {
-    pub description: String,
-    pub token: OldAccountId,
-    pub start_time: U64,
-    pub duration: U64,
-    pub claimer: HashMap<AccountId, Balance>,
+impl VersionedBounty {
+    pub fn get_claim_amount(&self, account_id: &AccountId) -> Balance {
+        match self {
+            VersionedBounty::Default(bounty) => bounty.claimer.get(account_id).cloned(),
+            VersionedBounty::V2(bounty) => bounty.claimer.get(account_id),
+        }
+        .unwrap_or(0)
+    }
 }
 
-impl From<BountyInput> for Bounty {
-    fn from(input: BountyInput) -> Self {
+impl Bounty {
+    pub fn new(id: u64, input: BountyInput) -> Self {
         assert!(input.duration.0 > 1000000000 * 60 * 2, "DURATION_MIN_ERROR");
         let mut total = 0u128;
-        for (_, value) in input.claimer.clone().into_iter() {
-            total += value
+        let mut claimer = LookupMap::new(StorageKeys::BountyClaimers { bounty_id: id });
+        for (account_id, value) in input.claimer.iter() {
+            total += value;
+            claimer.insert(account_id, value);
         };
 
         let BountyInput {
@@ -51,7 +77,7 @@ impl From<BountyInput> for Bounty {
             token,
             start_time,
             duration,
-            claimer,
+            claimer: _,
         } = input;
 
         Self {
@@ -64,12 +90,8 @@ impl From<BountyInput> for Bounty {
             claimer
         }
     }
-}
 
-
-
-impl Bounty {
-    pub fn claim(&mut self, account_id: &AccountId) -> Self {
+    pub fn claim(&mut self, account_id: &AccountId) {
         let expired_time = self.start_time.0 + self.duration.0;
         assert!(env::block_timestamp() < expired_time, "BOUNTY_DID_NOT_EXPIRED");
         let balance_option = self.claimer.remove(account_id);
@@ -84,13 +106,12 @@ impl Bounty {
             ONE_YOCTO_NEAR,
             GAS_FOR_FT_TRANSFER
         );
-        self.clone()
     }
 
     pub fn withdraw_the_rest(&mut self, receiver_id: &AccountId) {
         let expired_time = self.start_time.0 + self.duration.0;
         assert!(env::block_timestamp() > expired_time, "BOUNTY_DID_NOT_EXPIRED");
-        let rest_balance = self.rest.clone();
+        let rest_balance = self.rest;
         self.rest = 0;
         ext_fungible_token::ft_transfer(
             receiver_id.to_string(),
@@ -105,32 +126,42 @@ impl Bounty {
 
 impl Contract {
     pub fn create_bounty(&mut self, bounty_input: BountyInput) -> u64 {
-        let bounty = Bounty::from(bounty_input);
         let id = self.last_bounty_id;
+        let bounty = Bounty::new(id, bounty_input);
         self.bounties
-            .insert(&id, &VersionedBounty::Default(bounty.into()));
+            .insert(&id, &VersionedBounty::V2(bounty));
         self.last_bounty_id += 1;
         id
     }
+
+    // Returns bounty in the current layout, upgrading it if it was stored by an older version.
+    // Caller is responsible for writing it back.
+    pub(crate) fn internal_get_bounty(&mut self, id: u64) -> Bounty {
+        match self.bounties.get(&id).expect("BOUNTY_NOT_FOUND") {
+            VersionedBounty::Default(bounty) => bounty.upgrade(id),
+            VersionedBounty::V2(bounty) => bounty,
+        }
+    }
 }
 
 #[near_bindgen]
 impl Contract {
-    pub fn with_draw_bounty_rest(&self, bounty_id: u64) {
+    pub fn with_draw_bounty_rest(&mut self, bounty_id: u64) {
         let account_id = env::predecessor_account_id();
         assert_eq!(
             account_id,
             self.owner_id,
             "ONLY_OWNER"
         );
-        let mut bounty: Bounty = self.bounties.get(&bounty_id).expect("BOUNTY_NOT_FOUND").into();
+        let mut bounty = self.internal_get_bounty(bounty_id);
         bounty.withdraw_the_rest(&account_id);
+        self.bounties.insert(&bounty_id, &VersionedBounty::V2(bounty));
     }
 
     pub fn claim_bounty(&mut self,  bounty_id: u64) {
         let account_id = env::predecessor_account_id();
-        let mut bounty: Bounty = self.bounties.get(&bounty_id).expect("BOUNTY_NOT_FOUND").into();
-        let new_bounty = bounty.claim(&account_id);
-        self.bounties.insert(&bounty_id, &VersionedBounty::Default(new_bounty.into()));
+        let mut bounty = self.internal_get_bounty(bounty_id);
+        bounty.claim(&account_id);
+        self.bounties.insert(&bounty_id, &VersionedBounty::V2(bounty));
     }
-}
\ No newline at end of file
+}
@@ -21,7 +21,7 @@ impl Contract {
     }
 
     pub fn internal_reduce_delegation(&mut self, account_id: &AccountId, amount: U128) {
-        let prev_amount = self.delegations.get(&account_id).unwrap_or_default();
+        let prev_amount = self.delegations.get(account_id).unwrap_or_default();
         assert!(prev_amount >= amount.0, "ERR_NOT_ACCOUNT_NOT_ENOUGH_BALANCE");
         let new_amount = prev_amount - amount.0;
         self.delegations.insert(&account_id.to_string(), &new_amount);
@@ -39,8 +39,13 @@ impl Contract {
     pub fn register_delegation(&mut self, account_id: &AccountId) {
         let token_account = self.token_account.clone();
         assert_account_id(&token_account);
-        assert_eq!(env::attached_deposit(), 16 * env::storage_byte_cost());
-        self.delegations.insert(account_id, &0);
+        // Attached deposit is credited to the account's storage balance, which pays for the entry.
+        self.internal_storage_deposit(account_id, env::attached_deposit());
+        let initial_storage_usage = env::storage_usage();
+        if self.delegations.get(account_id).is_none() {
+            self.delegations.insert(account_id, &0);
+        }
+        self.internal_charge_storage(account_id, initial_storage_usage);
     }
 
     
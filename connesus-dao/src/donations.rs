@@ -1,9 +1,39 @@
 use crate::*;
 
+// Donors ordered by donated amount, so top donors are read from the end without sorting.
+pub type DonationRanking = TreeMap<(Balance, AccountId), ()>;
+
+// Moves donor to the position of its new total donation.
+pub(crate) fn update_donation_ranking(
+    ranking: &mut DonationRanking,
+    account_id: &AccountId,
+    prev_amount: Balance,
+    new_amount: Balance,
+) {
+    if prev_amount > 0 {
+        ranking.remove(&(prev_amount, account_id.clone()));
+    }
+    ranking.insert(&(new_amount, account_id.clone()), &());
+}
+
+// Returns donors with the highest donations first, in paginated view.
+pub(crate) fn top_donors(ranking: &DonationRanking, from_index: u64, limit: u64) -> Vec<ProposalDonateAsObject> {
+    ranking
+        .iter_rev()
+        .skip(from_index as usize)
+        .take(limit as usize)
+        .map(|((balance, account_id), _)| ProposalDonateAsObject {
+            account: account_id,
+            doate_balance: balance,
+        })
+        .collect()
+}
+
 impl Contract {
     pub fn open_donate(&mut self, account_id: &AccountId, amount: U128) {
         let prev_amount = self.donations.get(account_id).unwrap_or_default();
         let new_amount = prev_amount + amount.0;
         self.donations.insert(account_id, &new_amount);
+        update_donation_ranking(&mut self.open_donation_ranking, account_id, prev_amount, new_amount);
     }
-}
\ No newline at end of file
+}
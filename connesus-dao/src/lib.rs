@@ -1,10 +1,10 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap};
+use near_sdk::collections::{LookupMap, TreeMap, UnorderedMap, Vector};
 use near_sdk::json_types::{U128, U64, ValidAccountId};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    env, ext_contract, near_bindgen, AccountId, Balance, BorshStorageKey,
-    PanicOnDefault, PromiseOrValue, Gas, testing_env
+    assert_one_yocto, env, ext_contract, near_bindgen, AccountId, Balance, BorshStorageKey,
+    PanicOnDefault, Promise, PromiseOrValue, PromiseResult, Gas, StorageUsage
 };
 use std::collections::HashMap;
 use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
@@ -15,10 +15,12 @@ near_sdk::setup_alloc!();
 pub use crate::proposals::*;
 pub use crate::types::*;
 pub use crate::views::*;
-pub use crate::types::*;
 pub use crate::donations::*;
 pub use crate::bounty::*;
+pub use crate::storage::*;
+pub use crate::migrations::*;
 use crate::utils::*;
+pub use connesus_common::{BountyInput, DaoMetadata, OldDaoMetadata, TransferArgs, TransferPurpose};
 
 mod delegation;
 mod proposals;
@@ -26,6 +28,9 @@ mod types;
 pub mod views;
 mod donations;
 mod bounty;
+mod storage;
+mod migrations;
+mod upgrade;
 mod utils;
 
 #[derive(BorshStorageKey, BorshSerialize)]
@@ -33,7 +38,22 @@ pub enum StorageKeys {
     Delegations,
     Proposals,
     Donations,
-    Bounties
+    Bounties,
+    ProposalsByStatus,
+    ProposalsByKind,
+    ProposalsByProposer,
+    ProposalStatusIndex { status: String },
+    ProposalKindIndex { kind: String },
+    ProposalProposerIndex { account_id: AccountId },
+    AccountVotes,
+    AccountVotesIndex { account_id: AccountId },
+    ProposalVotes { proposal_id: u64 },
+    ProposalDonations { proposal_id: u64 },
+    StorageAccounts,
+    OpenDonationRanking,
+    ProposalDonationRanking { proposal_id: u64 },
+    BountyClaimers { bounty_id: u64 },
+    ActiveProposals,
 }
 
 #[near_bindgen]
@@ -64,13 +84,45 @@ pub struct Contract {
     pub last_bounty_id: u64,
 
     pub bounties: LookupMap<u64, VersionedBounty>,
+
+    // Proposal ids grouped by status label, in creation order.
+    pub proposals_by_status: LookupMap<String, TreeMap<u64, ()>>,
+    // Ids of proposals in progress keyed by the end of their voting period.
+    pub active_proposals: TreeMap<(u64, u64), ()>,
+    // Proposal ids grouped by kind label, in creation order.
+    pub proposals_by_kind: LookupMap<String, Vector<u64>>,
+    // Proposal ids grouped by proposer, in creation order.
+    pub proposals_by_proposer: LookupMap<AccountId, Vector<u64>>,
+    // Proposal ids each account has voted on, in order of first vote.
+    pub account_votes: LookupMap<AccountId, Vector<u64>>,
+    // Storage deposits of voters and donors (NEP-145).
+    pub storage_accounts: LookupMap<AccountId, StorageAccount>,
+    // Cost of registering an account for storage.
+    pub storage_balance_cost: Balance,
+    // Open donors ordered by donated amount.
+    pub open_donation_ranking: DonationRanking,
+    // Range of proposal ids left by version 1 that are not in the secondary indexes yet.
+    pub pending_indexes: Option<(u64, u64)>,
+    // Factory that created this DAO, i.e. the parent account that initialized it. Only it can
+    // upgrade the code and change the owner on the owner's behalf. None if the DAO was
+    // initialized by any other account.
+    pub factory_id: Option<AccountId>,
+    // Version of the state layout, see `migrations`.
+    pub state_version: u32,
 }
 
 #[near_bindgen]
 impl Contract {
     #[init]
     pub fn new(metadata: DaoMetadata, token_contract_id: AccountId, owner_id: AccountId) -> Self {
-        let this = Self {
+        metadata.assert_valid();
+        let factory_id = env::current_account_id()
+            .split_once('.')
+            .map(|(_, parent_id)| parent_id.to_string())
+            .filter(|parent_id| parent_id == &env::predecessor_account_id());
+        let mut storage_accounts = LookupMap::new(StorageKeys::StorageAccounts);
+        let storage_balance_cost = Self::measure_storage_balance_cost(&mut storage_accounts);
+        Self {
             dao_metadata: metadata,
             token_account: token_contract_id,
             total_delegation_amount: 0,
@@ -79,54 +131,47 @@ impl Contract {
             proposals: LookupMap::new(StorageKeys::Proposals),
             locked_amount: 0,
             donations: LookupMap::new(StorageKeys::Donations),
-            owner_id: owner_id,
+            owner_id,
             last_bounty_id: 0,
             bounties: LookupMap::new(StorageKeys::Bounties),
-        };
-        this
+            proposals_by_status: LookupMap::new(StorageKeys::ProposalsByStatus),
+            active_proposals: TreeMap::new(StorageKeys::ActiveProposals),
+            proposals_by_kind: LookupMap::new(StorageKeys::ProposalsByKind),
+            proposals_by_proposer: LookupMap::new(StorageKeys::ProposalsByProposer),
+            account_votes: LookupMap::new(StorageKeys::AccountVotes),
+            storage_accounts,
+            storage_balance_cost,
+            open_donation_ranking: TreeMap::new(StorageKeys::OpenDonationRanking),
+            pending_indexes: None,
+            factory_id,
+            state_version: STATE_VERSION,
+        }
     }
 
-    
+
+    // Upgrades state written by any previous version of the contract to the current layout.
+    // Version 1 did not record its factory, so `factory_id` must be given when migrating from it
+    // for the DAO to accept calls from the factory. It is ignored for the current layout.
     #[init(ignore_state)]
-    pub fn migrate() -> Self {
+    pub fn migrate(factory_id: Option<ValidAccountId>) -> Self {
         assert_eq!(
             env::predecessor_account_id(),
             env::current_account_id(),
             "ERR_NOT_ALLOWED"
         );
-        let this: Contract = env::state_read().expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
-        // Self {
-        //     dao_metadata: this.dao_metadata,
-        //     token_account: this.token_account,
-        //     total_delegation_amount: 0,
-        //     delegations: LookupMap::new(StorageKeys::Delegations),
-        //     last_proposal_id: 0,
-        //     proposals: LookupMap::new(StorageKeys::Proposals),
-        //     locked_amount: 0,
-        //     donations: LookupMap::new(StorageKeys::Donations),
-        //     owner_id: this.owner_id,
-        //     last_bounty_id: 0,
-        //     bounties: LookupMap::new(StorageKeys::Bounties),
-        // }
-        this
+        let state = env::storage_read(b"STATE").expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
+        if let Ok(this) = Contract::try_from_slice(&state) {
+            assert_eq!(this.state_version, STATE_VERSION, "ERR_UNKNOWN_STATE_VERSION");
+            return this;
+        }
+        let old = OldContractV1::try_from_slice(&state).expect("ERR_UNKNOWN_STATE_VERSION");
+        Contract {
+            factory_id: factory_id.map(|account_id| account_id.into()),
+            ..Contract::from(old)
+        }
     }
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
-#[serde(crate = "near_sdk::serde")]
-pub enum TransferPurpose {
-    Delegate(AccountId),
-    OpenDonate,
-    ProposalDonate(u64),
-    CreateBounty(BountyInput),
-}
-
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
-#[serde(crate = "near_sdk::serde")]
-pub struct TransferArgs {
-    pub purpose: TransferPurpose, // 1 for delegate, 2 for open donate, 3 for proposal donate,
-}
-
 /**
     Delegate for user
     User transfer token to dao contract
@@ -151,15 +196,19 @@ impl FungibleTokenReceiver for Contract {
             },
             TransferPurpose::OpenDonate => {
                 assert_account_id(&token_account);
+                let initial_storage_usage = env::storage_usage();
                 self.open_donate(&sender_id.to_string(), amount);
+                self.internal_charge_storage(&sender_id.to_string(), initial_storage_usage);
             }, 
             TransferPurpose::ProposalDonate(proposal_id) => {
                 assert_account_id(&token_account);
-                let mut proposal_obj: Proposal = self.proposals.get(&proposal_id).expect("ERR_NO_PROPOSAL").into();
+                let mut proposal_obj = self.internal_get_proposal(proposal_id);
                 match proposal_obj.kind {
                     ProposalKind::Donate => {
-                        let new_proposal = proposal_obj.donate(&sender_id.to_string(), amount.0);
-                        self.proposals.insert(&proposal_id, &VersionedProposal::Default(new_proposal));
+                        let initial_storage_usage = env::storage_usage();
+                        proposal_obj.donate(&sender_id.to_string(), amount.0);
+                        self.proposals.insert(&proposal_id, &VersionedProposal::V2(proposal_obj));
+                        self.internal_charge_storage(&sender_id.to_string(), initial_storage_usage);
                     },
                     _ => {
                         assert!(
@@ -173,7 +222,7 @@ impl FungibleTokenReceiver for Contract {
             TransferPurpose::CreateBounty(bounty_input) => {
                 assert_account_id(&bounty_input.token);
                 let mut total_token_receive = 0;
-                for (key, value) in &bounty_input.claimer {
+                for value in bounty_input.claimer.values() {
                     total_token_receive += value;
                 }
                 assert_eq!(
@@ -192,3 +241,481 @@ impl FungibleTokenReceiver for Contract {
         PromiseOrValue::Value(U128(0))
     }
 }
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::test_utils::{VMContextBuilder, accounts};
+    use near_sdk::{testing_env, MockedBlockchain, RuntimeFeesConfig, VMConfig};
+
+    const DAY: u64 = 1_000_000_000 * 60 * 60 * 24;
+
+    fn get_context(predecessor: ValidAccountId) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(accounts(0))
+            .signer_account_id(predecessor.clone())
+            .predecessor_account_id(predecessor);
+        builder
+    }
+
+    // Account of a DAO created by the factory accounts(5).
+    fn dao_of_factory() -> ValidAccountId {
+        ValidAccountId::try_from(format!("dao.{}", accounts(5))).unwrap()
+    }
+
+    fn factory_context(predecessor: ValidAccountId) -> VMContextBuilder {
+        let mut builder = get_context(predecessor);
+        builder.current_account_id(dao_of_factory());
+        builder
+    }
+
+    fn metadata() -> DaoMetadata {
+        DaoMetadata {
+            name: "Test".to_string(),
+            purpose: "to test".to_string(),
+            thumbnail: "".to_string(),
+            symbol: "test".to_string(),
+            facebook: None,
+            twitter: None,
+            discord: None,
+            instagram: None,
+            youtube: None,
+        }
+    }
+
+    // Sets up a DAO deployed by its own account, owned by accounts(1), with accounts(1) and
+    // accounts(2) holding delegations. Leaves accounts(1) as predecessor.
+    fn setup_contract() -> Contract {
+        testing_env!(get_context(accounts(0)).build());
+        let mut contract = Contract::new(metadata(), accounts(4).to_string(), accounts(1).to_string());
+        testing_env!(get_context(accounts(1)).build());
+        contract.delegations.insert(&accounts(1).to_string(), &10);
+        contract.delegations.insert(&accounts(2).to_string(), &5);
+        contract
+    }
+
+    fn proposal_input(kind: ProposalKind, duration: u64) -> ProposalInput {
+        let mut options = HashMap::new();
+        for option in &["yes", "no"] {
+            options.insert(option.to_string(), VoteOption {
+                title: option.to_string(),
+                description: "".to_string(),
+                min_vote_weight: 0,
+            });
+        }
+        ProposalInput {
+            description: "proposal".to_string(),
+            kind,
+            duration: U64(duration),
+            options,
+        }
+    }
+
+    // Registers storage of given account with enough deposit for a few votes and donations.
+    fn register_storage(contract: &mut Contract, account_id: ValidAccountId) {
+        testing_env!(get_context(account_id)
+            .attached_deposit(contract.storage_balance_bounds().min.0 * 10)
+            .build());
+        contract.storage_deposit(None, None);
+    }
+
+    fn vote(contract: &mut Contract, account_id: ValidAccountId, id: u64, option_id: &str) {
+        testing_env!(get_context(account_id).build());
+        contract.act_proposal(id, Action::Vote { option_id: option_id.to_string() });
+    }
+
+    // Calls `ft_on_transfer` as the DAO token would on a transfer from `sender`.
+    fn transfer_call(contract: &mut Contract, sender: ValidAccountId, amount: Balance, purpose: TransferPurpose) {
+        testing_env!(get_context(accounts(4)).build());
+        let msg = near_sdk::serde_json::to_string(&TransferArgs { purpose }).unwrap();
+        contract.ft_on_transfer(sender, U128(amount), msg);
+    }
+
+    fn top_donors(contract: &Contract, proposal_id: Option<u64>, from_index: u64, limit: u64) -> Vec<(String, Balance)> {
+        contract
+            .get_top_donors(proposal_id, from_index, limit)
+            .into_iter()
+            .map(|donor| (donor.account, donor.doate_balance))
+            .collect()
+    }
+
+    fn vote_kind() -> ProposalKind {
+        ProposalKind::Vote { vote_kind: VoteKind::VoteByDelegation }
+    }
+
+    fn filter(status: Option<ProposalStatus>, kind: Option<&str>, active_only: bool) -> ProposalFilter {
+        ProposalFilter {
+            status,
+            kind: kind.map(|kind| kind.to_string()),
+            proposer: None,
+            active_only: Some(active_only),
+        }
+    }
+
+    fn ids(proposals: Vec<ProposalOutput>) -> Vec<u64> {
+        proposals.into_iter().map(|proposal| proposal.id).collect()
+    }
+
+    #[test]
+    fn test_filtered_proposals_keep_creation_order() {
+        let mut contract = setup_contract();
+        for _ in 0..4 {
+            contract.add_proposal(proposal_input(vote_kind(), DAY));
+        }
+        contract.add_proposal(proposal_input(ProposalKind::Donate, DAY));
+        contract.act_proposal(0, Action::Finalize);
+
+        let in_progress = Some(ProposalStatus::InProgress);
+        assert_eq!(ids(contract.get_filtered_proposals(filter(in_progress.clone(), None, false), 0, 10, None)), vec![1, 2, 3, 4]);
+        assert_eq!(ids(contract.get_filtered_proposals(filter(in_progress.clone(), None, false), 1, 2, None)), vec![2, 3]);
+        assert_eq!(ids(contract.get_filtered_proposals(filter(in_progress, Some("vote"), false), 0, 10, None)), vec![1, 2, 3]);
+        assert_eq!(ids(contract.get_filtered_proposals(filter(Some(ProposalStatus::Expired), None, false), 0, 10, None)), vec![0]);
+        assert_eq!(ids(contract.get_filtered_proposals(filter(None, Some("donate"), false), 0, 10, None)), vec![4]);
+        assert!(contract.get_filtered_proposals(filter(None, Some("unknown"), false), 0, 10, None).is_empty());
+    }
+
+    #[test]
+    fn test_filtered_proposals_active_only() {
+        let mut contract = setup_contract();
+        contract.add_proposal(proposal_input(vote_kind(), 3 * DAY));
+        contract.add_proposal(proposal_input(vote_kind(), DAY));
+        contract.add_proposal(proposal_input(vote_kind(), 2 * DAY));
+        contract.add_proposal(proposal_input(vote_kind(), 4 * DAY));
+        contract.act_proposal(3, Action::Finalize);
+
+        assert_eq!(ids(contract.get_filtered_proposals(filter(None, None, true), 0, 10, None)), vec![1, 2, 0]);
+        assert_eq!(ids(contract.get_filtered_proposals(filter(None, None, true), 1, 1, None)), vec![2]);
+
+        testing_env!(get_context(accounts(1)).block_timestamp(DAY + 1).build());
+        assert_eq!(ids(contract.get_filtered_proposals(filter(None, None, true), 0, 10, None)), vec![2, 0]);
+        // Ended proposals stay in progress until finalized.
+        assert_eq!(
+            ids(contract.get_filtered_proposals(filter(Some(ProposalStatus::InProgress), None, false), 0, 10, None)),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_account_votes_repeat_vote_keeps_single_entry() {
+        let mut contract = setup_contract();
+        contract.add_proposal(proposal_input(vote_kind(), DAY));
+        register_storage(&mut contract, accounts(2));
+        vote(&mut contract, accounts(2), 0, "yes");
+        vote(&mut contract, accounts(2), 0, "no");
+
+        let votes = contract.get_account_votes(accounts(2).to_string(), 0, 10);
+        assert_eq!(votes.len(), 1);
+        assert_eq!(votes[0].proposal_id, 0);
+        assert_eq!(votes[0].vote.option, "no");
+        assert_eq!(votes[0].vote.delegations, 5);
+    }
+
+    #[test]
+    fn test_account_votes_pagination() {
+        let mut contract = setup_contract();
+        for _ in 0..3 {
+            contract.add_proposal(proposal_input(vote_kind(), DAY));
+        }
+        register_storage(&mut contract, accounts(2));
+        for id in &[2, 0, 1] {
+            vote(&mut contract, accounts(2), *id, "yes");
+        }
+
+        let page = |from_index, limit| -> Vec<u64> {
+            contract
+                .get_account_votes(accounts(2).to_string(), from_index, limit)
+                .into_iter()
+                .map(|vote| vote.proposal_id)
+                .collect()
+        };
+        assert_eq!(page(0, 10), vec![2, 0, 1]);
+        assert_eq!(page(1, 1), vec![0]);
+        assert_eq!(page(2, u64::MAX), vec![1]);
+        assert!(page(5, 10).is_empty());
+        assert!(contract.get_account_votes(accounts(3).to_string(), 0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_vote_aggregates() {
+        let mut contract = setup_contract();
+        contract.add_proposal(proposal_input(vote_kind(), DAY));
+        contract.add_proposal(proposal_input(ProposalKind::Vote { vote_kind: VoteKind::MajorityVote }, DAY));
+        register_storage(&mut contract, accounts(1));
+        register_storage(&mut contract, accounts(2));
+        for id in 0..2 {
+            vote(&mut contract, accounts(1), id, "yes");
+            vote(&mut contract, accounts(2), id, "yes");
+            vote(&mut contract, accounts(2), id, "no");
+        }
+
+        let by_delegation = contract.get_proposal(0, None).unwrap().proposal;
+        assert_eq!(by_delegation.total_delegation_amount, 15);
+        assert_eq!(by_delegation.option_delegations["yes"], 10);
+        assert_eq!(by_delegation.option_delegations["no"], 5);
+
+        let by_majority = contract.get_proposal(1, Some(accounts(2).to_string())).unwrap();
+        assert_eq!(by_majority.proposal.total_delegation_amount, 2);
+        assert_eq!(by_majority.proposal.option_delegations["yes"], 1);
+        assert_eq!(by_majority.proposal.option_delegations["no"], 1);
+        assert_eq!(by_majority.user_select.option, "no");
+    }
+
+    #[test]
+    fn test_donation_aggregates() {
+        let mut contract = setup_contract();
+        contract.add_proposal(proposal_input(ProposalKind::Donate, DAY));
+        register_storage(&mut contract, accounts(2));
+        register_storage(&mut contract, accounts(3));
+        transfer_call(&mut contract, accounts(2), 10, TransferPurpose::ProposalDonate(0));
+        transfer_call(&mut contract, accounts(3), 4, TransferPurpose::ProposalDonate(0));
+        transfer_call(&mut contract, accounts(2), 5, TransferPurpose::ProposalDonate(0));
+        transfer_call(&mut contract, accounts(3), 7, TransferPurpose::OpenDonate);
+
+        assert_eq!(contract.get_proposal(0, None).unwrap().proposal.total_donations, 19);
+        let proposal = contract.internal_get_proposal(0);
+        assert_eq!(proposal.donations.get(&accounts(2).to_string()), Some(15));
+        assert_eq!(proposal.donations.get(&accounts(3).to_string()), Some(4));
+        assert_eq!(proposal.donations.len(), 2);
+        assert_eq!(contract.get_donation_balance(accounts(3).to_string()), Some(7));
+        assert_eq!(contract.get_donation_balance(accounts(2).to_string()), None);
+    }
+
+    #[test]
+    fn test_storage_deposit() {
+        let mut contract = setup_contract();
+        let min = contract.storage_balance_bounds().min.0;
+        testing_env!(get_context(accounts(2)).attached_deposit(min * 3).build());
+        let balance = contract.storage_deposit(None, Some(true));
+        assert_eq!(balance.total.0, min);
+        assert_eq!(balance.available.0, 0);
+
+        testing_env!(get_context(accounts(3)).attached_deposit(min).build());
+        let balance = contract.storage_deposit(Some(accounts(2)), None);
+        assert_eq!(balance.total.0, 2 * min);
+        assert_eq!(balance.available.0, min);
+
+        testing_env!(get_context(accounts(2)).attached_deposit(min).build());
+        let balance = contract.storage_deposit(None, Some(true));
+        assert_eq!(balance.total.0, 2 * min);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_TOO_LOW")]
+    fn test_storage_deposit_below_minimum() {
+        let mut contract = setup_contract();
+        let min = contract.storage_balance_bounds().min.0;
+        testing_env!(get_context(accounts(2)).attached_deposit(min - 1).build());
+        contract.storage_deposit(None, None);
+    }
+
+    #[test]
+    fn test_storage_charged_on_vote_and_donation() {
+        let mut contract = setup_contract();
+        contract.add_proposal(proposal_input(vote_kind(), DAY));
+        contract.add_proposal(proposal_input(ProposalKind::Donate, DAY));
+        register_storage(&mut contract, accounts(2));
+        let available = |contract: &Contract| contract.storage_balance_of(accounts(2)).unwrap().available.0;
+        let initial = available(&contract);
+
+        vote(&mut contract, accounts(2), 0, "yes");
+        let after_vote = available(&contract);
+        assert!(after_vote < initial);
+        let used_bytes = contract.storage_accounts.get(&accounts(2).to_string()).unwrap().used_bytes;
+        assert_eq!(Balance::from(used_bytes) * env::storage_byte_cost(), initial - after_vote);
+
+        transfer_call(&mut contract, accounts(2), 10, TransferPurpose::ProposalDonate(1));
+        let after_donation = available(&contract);
+        assert!(after_donation < after_vote);
+
+        // A repeat donation only updates existing entries.
+        transfer_call(&mut contract, accounts(2), 10, TransferPurpose::ProposalDonate(1));
+        assert_eq!(available(&contract), after_donation);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_WRONG_PROPOSAL_KIND")]
+    fn test_vote_on_donate_proposal() {
+        let mut contract = setup_contract();
+        contract.add_proposal(proposal_input(ProposalKind::Donate, DAY));
+        register_storage(&mut contract, accounts(2));
+        vote(&mut contract, accounts(2), 0, "yes");
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STORAGE_NOT_REGISTERED")]
+    fn test_vote_requires_storage_registration() {
+        let mut contract = setup_contract();
+        contract.add_proposal(proposal_input(vote_kind(), DAY));
+        vote(&mut contract, accounts(2), 0, "yes");
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_STORAGE_BALANCE")]
+    fn test_donation_requires_storage_balance() {
+        let mut contract = setup_contract();
+        contract.add_proposal(proposal_input(ProposalKind::Donate, DAY));
+        let min = contract.storage_balance_bounds().min.0;
+        testing_env!(get_context(accounts(2)).attached_deposit(min).build());
+        contract.storage_deposit(None, None);
+        transfer_call(&mut contract, accounts(2), 10, TransferPurpose::ProposalDonate(0));
+    }
+
+    #[test]
+    fn test_storage_unregister_unused() {
+        let mut contract = setup_contract();
+        register_storage(&mut contract, accounts(2));
+        testing_env!(get_context(accounts(2)).attached_deposit(1).build());
+        assert!(contract.storage_unregister(None));
+        assert!(contract.storage_balance_of(accounts(2)).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STORAGE_IN_USE")]
+    fn test_storage_unregister_refused_after_vote() {
+        let mut contract = setup_contract();
+        contract.add_proposal(proposal_input(vote_kind(), DAY));
+        register_storage(&mut contract, accounts(2));
+        vote(&mut contract, accounts(2), 0, "yes");
+        testing_env!(get_context(accounts(2)).attached_deposit(1).build());
+        contract.storage_unregister(None);
+    }
+
+    #[test]
+    fn test_top_donors_reranked_on_repeat_donation() {
+        let mut contract = setup_contract();
+        contract.add_proposal(proposal_input(ProposalKind::Donate, DAY));
+        for account_id in &[accounts(2), accounts(3)] {
+            register_storage(&mut contract, account_id.clone());
+        }
+        for proposal_id in &[None, Some(0)] {
+            let purpose = || match proposal_id {
+                Some(id) => TransferPurpose::ProposalDonate(*id),
+                None => TransferPurpose::OpenDonate,
+            };
+            transfer_call(&mut contract, accounts(2), 10, purpose());
+            transfer_call(&mut contract, accounts(3), 20, purpose());
+            transfer_call(&mut contract, accounts(2), 15, purpose());
+        }
+
+        let expected = vec![(accounts(2).to_string(), 25), (accounts(3).to_string(), 20)];
+        assert_eq!(top_donors(&contract, None, 0, 10), expected);
+        assert_eq!(top_donors(&contract, Some(0), 0, 10), expected);
+        assert_eq!(contract.open_donation_ranking.len(), 2);
+    }
+
+    #[test]
+    fn test_top_donors_ties_and_pagination() {
+        let mut contract = setup_contract();
+        for account_id in &[accounts(2), accounts(3), accounts(5)] {
+            register_storage(&mut contract, account_id.clone());
+        }
+        transfer_call(&mut contract, accounts(3), 10, TransferPurpose::OpenDonate);
+        transfer_call(&mut contract, accounts(2), 10, TransferPurpose::OpenDonate);
+        transfer_call(&mut contract, accounts(5), 30, TransferPurpose::OpenDonate);
+
+        // Equal amounts are ordered by account id, descending.
+        assert_eq!(
+            top_donors(&contract, None, 0, 10),
+            vec![
+                (accounts(5).to_string(), 30),
+                (accounts(3).to_string(), 10),
+                (accounts(2).to_string(), 10),
+            ]
+        );
+        assert_eq!(top_donors(&contract, None, 1, 1), vec![(accounts(3).to_string(), 10)]);
+        assert!(top_donors(&contract, None, 3, 10).is_empty());
+        assert!(top_donors(&contract, None, u64::MAX, 10).is_empty());
+    }
+
+    #[test]
+    fn test_factory_recorded_on_new() {
+        testing_env!(factory_context(accounts(5)).build());
+        let mut contract = Contract::new(metadata(), accounts(4).to_string(), accounts(1).to_string());
+        assert_eq!(contract.factory_id, Some(accounts(5).to_string()));
+
+        contract.set_owner_from_factory(accounts(2));
+        assert_eq!(contract.get_owner(), accounts(2).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ALLOWED")]
+    fn test_set_owner_from_other_account() {
+        testing_env!(factory_context(accounts(5)).build());
+        let mut contract = Contract::new(metadata(), accounts(4).to_string(), accounts(1).to_string());
+        testing_env!(factory_context(accounts(3)).build());
+        contract.set_owner_from_factory(accounts(3));
+    }
+
+    #[test]
+    fn test_factory_not_recorded_for_other_deployer() {
+        testing_env!(get_context(accounts(5)).build());
+        let contract = Contract::new(metadata(), accounts(4).to_string(), accounts(1).to_string());
+        assert_eq!(contract.factory_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ALLOWED")]
+    fn test_update_from_factory_without_factory() {
+        testing_env!(get_context(accounts(0)).build());
+        let contract = Contract::new(metadata(), accounts(4).to_string(), accounts(1).to_string());
+        assert_eq!(contract.factory_id, None);
+        testing_env!(get_context(accounts(5)).build());
+        contract.update_from_factory();
+    }
+
+    fn updated_metadata() -> DaoMetadata {
+        DaoMetadata {
+            name: "Updated".to_string(),
+            ..metadata()
+        }
+    }
+
+    #[test]
+    fn test_update_metadata_applied_after_factory() {
+        testing_env!(factory_context(accounts(5)).build());
+        let mut contract = Contract::new(metadata(), accounts(4).to_string(), accounts(1).to_string());
+        testing_env!(factory_context(accounts(1)).attached_deposit(10).build());
+        contract.update_metadata(updated_metadata());
+        assert_eq!(contract.get_metadata().name, "Test");
+
+        testing_env!(
+            factory_context(dao_of_factory()).build(),
+            VMConfig::default(),
+            RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        assert!(contract.on_update_metadata(updated_metadata(), accounts(1).to_string(), U128(10)));
+        assert_eq!(contract.get_metadata().name, "Updated");
+    }
+
+    #[test]
+    fn test_update_metadata_rejected_by_factory() {
+        testing_env!(factory_context(accounts(5)).build());
+        let mut contract = Contract::new(metadata(), accounts(4).to_string(), accounts(1).to_string());
+        testing_env!(factory_context(accounts(1)).attached_deposit(10).build());
+        contract.update_metadata(updated_metadata());
+
+        testing_env!(
+            factory_context(dao_of_factory()).build(),
+            VMConfig::default(),
+            RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert!(!contract.on_update_metadata(updated_metadata(), accounts(1).to_string(), U128(10)));
+        assert_eq!(contract.get_metadata().name, "Test");
+    }
+
+    #[test]
+    fn test_update_metadata_without_factory() {
+        let mut contract = setup_contract();
+        testing_env!(get_context(accounts(1)).attached_deposit(10).build());
+        contract.update_metadata(updated_metadata());
+        assert_eq!(contract.get_metadata().name, "Updated");
+    }
+}
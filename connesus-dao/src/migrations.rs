@@ -0,0 +1,375 @@
+use crate::*;
+
+// Version of the state layout written by this code.
+// 1: initial layout, proposals and bounties keep votes, donations and claimers inline.
+// 2: proposal indexes, storage staking and donation rankings; proposals and bounties are `V2`;
+//    DAO metadata has a youtube link.
+pub const STATE_VERSION: u32 = 2;
+
+// Contract state layout of version 1.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct OldContractV1 {
+    pub dao_metadata: OldDaoMetadata,
+    pub locked_amount: Balance,
+    pub token_account: OldAccountId,
+    pub total_delegation_amount: Balance,
+    pub delegations: LookupMap<AccountId, Balance>,
+    pub last_proposal_id: u64,
+    pub proposals: LookupMap<u64, VersionedProposal>,
+    pub donations: LookupMap<AccountId, Balance>,
+    pub owner_id: AccountId,
+    pub last_bounty_id: u64,
+    pub bounties: LookupMap<u64, VersionedBounty>,
+}
+
+// Proposals and bounties are left in their version 1 layout and upgraded lazily when modified.
+// Secondary indexes are filled in batches by `migrate_indexes`, or when a proposal is modified
+// before its batch, to keep the migration itself within gas limits. Open donations were not
+// iterable in version 1, so open donors only enter the ranking on their next donation.
+impl From<OldContractV1> for Contract {
+    fn from(old: OldContractV1) -> Self {
+        let mut storage_accounts = LookupMap::new(StorageKeys::StorageAccounts);
+        let storage_balance_cost = Self::measure_storage_balance_cost(&mut storage_accounts);
+        Self {
+            dao_metadata: old.dao_metadata.into(),
+            locked_amount: old.locked_amount,
+            token_account: old.token_account,
+            total_delegation_amount: old.total_delegation_amount,
+            delegations: old.delegations,
+            last_proposal_id: old.last_proposal_id,
+            proposals: old.proposals,
+            donations: old.donations,
+            owner_id: old.owner_id,
+            last_bounty_id: old.last_bounty_id,
+            bounties: old.bounties,
+            proposals_by_status: LookupMap::new(StorageKeys::ProposalsByStatus),
+            active_proposals: TreeMap::new(StorageKeys::ActiveProposals),
+            proposals_by_kind: LookupMap::new(StorageKeys::ProposalsByKind),
+            proposals_by_proposer: LookupMap::new(StorageKeys::ProposalsByProposer),
+            account_votes: LookupMap::new(StorageKeys::AccountVotes),
+            storage_accounts,
+            storage_balance_cost,
+            open_donation_ranking: TreeMap::new(StorageKeys::OpenDonationRanking),
+            pending_indexes: if old.last_proposal_id > 0 {
+                Some((0, old.last_proposal_id))
+            } else {
+                None
+            },
+            factory_id: None,
+            state_version: STATE_VERSION,
+        }
+    }
+}
+
+impl Contract {
+    // Returns true if given proposal was stored by version 1 and is not in the secondary indexes yet.
+    pub(crate) fn is_index_pending(&self, id: u64) -> bool {
+        matches!(self.pending_indexes, Some((from, to)) if from <= id && id < to)
+    }
+
+    // Adds proposal stored by version 1 and its voters to the secondary indexes.
+    pub(crate) fn internal_index_v1_proposal(&mut self, id: u64, proposal: &ProposalV1) {
+        self.internal_index_proposal(id, proposal.end_time(), &proposal.status, &proposal.kind, &proposal.proposer);
+        for account_id in proposal.votes.keys() {
+            self.internal_add_account_vote(account_id, id);
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Adds up to `limit` proposals left by version 1 to the secondary indexes.
+    // Returns true once all of them are indexed.
+    pub fn migrate_indexes(&mut self, limit: u64) -> bool {
+        assert_eq!(self.owner_id, env::predecessor_account_id(), "ONLY_OWNER");
+        let (from, to) = match self.pending_indexes {
+            Some(range) => range,
+            None => return true,
+        };
+        let end = std::cmp::min(to, from.saturating_add(limit));
+        for id in from..end {
+            // Proposals modified since the migration were indexed at that time.
+            if let Some(VersionedProposal::Default(proposal)) = self.proposals.get(&id) {
+                self.internal_index_v1_proposal(id, &proposal);
+            }
+        }
+        self.pending_indexes = if end < to { Some((end, to)) } else { None };
+        self.pending_indexes.is_none()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::test_utils::{VMContextBuilder, accounts};
+    use near_sdk::{testing_env, MockedBlockchain};
+
+    const DAY: u64 = 1_000_000_000 * 60 * 60 * 24;
+
+    fn get_context() -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(accounts(0))
+            .signer_account_id(accounts(0))
+            .predecessor_account_id(accounts(0));
+        builder
+    }
+
+    fn old_metadata() -> OldDaoMetadata {
+        OldDaoMetadata {
+            name: "Test".to_string(),
+            purpose: "to test".to_string(),
+            thumbnail: "".to_string(),
+            symbol: "test".to_string(),
+            facebook: Some("https://facebook.com".to_string()),
+            twitter: None,
+            discord: None,
+            instagram: None,
+        }
+    }
+
+    fn active_filter() -> ProposalFilter {
+        ProposalFilter { status: None, kind: None, proposer: None, active_only: Some(true) }
+    }
+
+    // Writes state as a version 1 contract would have left it: `proposal_count` vote proposals
+    // voted by accounts(1) and donated to by accounts(2), and one bounty for accounts(3).
+    fn write_v1_snapshot(proposal_count: u64) {
+        let mut proposals = LookupMap::new(StorageKeys::Proposals);
+        for id in 0..proposal_count {
+            let mut options = HashMap::new();
+            options.insert("yes".to_string(), VoteOption {
+                title: "Yes".to_string(),
+                description: "".to_string(),
+                min_vote_weight: 0,
+            });
+            let mut votes = HashMap::new();
+            votes.insert(accounts(1).to_string(), Vote { option: "yes".to_string(), delegations: 5 });
+            let mut option_delegations = HashMap::new();
+            option_delegations.insert("yes".to_string(), 5);
+            let mut donations = HashMap::new();
+            donations.insert(accounts(2).to_string(), 10);
+            proposals.insert(&id, &VersionedProposal::Default(ProposalV1 {
+                proposer: accounts(0).to_string(),
+                description: "v1 proposal".to_string(),
+                kind: ProposalKind::Vote { vote_kind: VoteKind::VoteByDelegation },
+                status: ProposalStatus::InProgress,
+                options,
+                submission_time: U64(0),
+                duration: U64(DAY),
+                donations,
+                total_donations: 10,
+                total_delegation_amount: 5,
+                votes,
+                option_delegations,
+            }));
+        }
+
+        let mut bounties = LookupMap::new(StorageKeys::Bounties);
+        let mut claimer = HashMap::new();
+        claimer.insert(accounts(3).to_string(), 7);
+        bounties.insert(&0, &VersionedBounty::Default(BountyV1 {
+            description: "v1 bounty".to_string(),
+            token: accounts(4).to_string(),
+            total: 7,
+            rest: 7,
+            start_time: U64(0),
+            duration: U64(DAY),
+            claimer,
+        }));
+
+        let mut delegations = LookupMap::new(StorageKeys::Delegations);
+        delegations.insert(&accounts(1).to_string(), &5);
+        delegations.insert(&accounts(2).to_string(), &3);
+
+        env::state_write(&OldContractV1 {
+            dao_metadata: old_metadata(),
+            locked_amount: 8,
+            token_account: accounts(4).to_string(),
+            total_delegation_amount: 8,
+            delegations,
+            last_proposal_id: proposal_count,
+            proposals,
+            donations: LookupMap::new(StorageKeys::Donations),
+            owner_id: accounts(0).to_string(),
+            last_bounty_id: 1,
+            bounties,
+        });
+    }
+
+    #[test]
+    fn test_migrate_from_v1() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(1);
+
+        let mut contract = Contract::migrate(None);
+        assert_eq!(contract.state_version, STATE_VERSION);
+        assert_eq!(contract.owner_id, accounts(0).to_string());
+        let metadata = contract.get_metadata();
+        assert_eq!(metadata.facebook, Some("https://facebook.com".to_string()));
+        assert_eq!(metadata.youtube, None);
+        assert_eq!(contract.last_proposal_id, 1);
+        assert_eq!(contract.delegation_balance_of(accounts(1).to_string()).0, 5);
+
+        let proposal = contract.get_proposal(0, Some(accounts(1).to_string())).unwrap();
+        assert_eq!(proposal.proposal.description, "v1 proposal");
+        assert_eq!(proposal.user_select.option, "yes");
+
+        assert!(contract.get_filtered_proposals(active_filter(), 0, 10, None).is_empty());
+        assert!(contract.migrate_indexes(10));
+        assert_eq!(contract.get_filtered_proposals(active_filter(), 0, 10, None).len(), 1);
+
+        let account_votes = contract.get_account_votes(accounts(1).to_string(), 0, 10);
+        assert_eq!(account_votes.len(), 1);
+        assert_eq!(account_votes[0].vote.delegations, 5);
+
+        let donors = contract.get_proposal_donation(0, 0, 10);
+        assert_eq!(donors.len(), 1);
+        assert_eq!(donors[0].doate_balance, 10);
+
+        let bounty = contract.get_bounty(0, Some(accounts(3).to_string())).unwrap();
+        assert_eq!(bounty.claim_amount, 7);
+    }
+
+    #[test]
+    fn test_migrate_is_noop_for_current_state() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(1);
+        let mut contract = Contract::migrate(None);
+        contract.migrate_indexes(10);
+        env::state_write(&contract);
+
+        let contract = Contract::migrate(None);
+        assert_eq!(contract.state_version, STATE_VERSION);
+        assert_eq!(contract.get_account_votes(accounts(1).to_string(), 0, 10).len(), 1);
+    }
+
+    #[test]
+    fn test_v1_proposal_upgraded_on_vote() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(1);
+        let mut contract = Contract::migrate(None);
+
+        testing_env!(get_context()
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(contract.storage_balance_bounds().min.0 * 10)
+            .build());
+        contract.storage_deposit(None, None);
+        contract.act_proposal(0, Action::Vote { option_id: "yes".to_string() });
+
+        match contract.proposals.get(&0).unwrap() {
+            VersionedProposal::V2(proposal) => {
+                assert_eq!(proposal.total_delegation_amount, 8);
+                assert_eq!(proposal.get_user_voted(&accounts(1).to_string()).unwrap().delegations, 5);
+                assert_eq!(proposal.donations.get(&accounts(2).to_string()), Some(10));
+            }
+            VersionedProposal::Default(_) => panic!("proposal was not upgraded"),
+        }
+        let donors = contract.get_top_donors(Some(0), 0, 10);
+        assert_eq!(donors[0].account, accounts(2).to_string());
+        assert_eq!(contract.get_account_votes(accounts(2).to_string(), 0, 10).len(), 1);
+    }
+
+    #[test]
+    fn test_v1_proposal_upgrade_not_charged_to_voter() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(3);
+        let mut contract = Contract::migrate(None);
+        contract.migrate_indexes(10);
+        for id in 1..3 {
+            let proposal = contract.internal_get_proposal(id);
+            contract.proposals.insert(&id, &VersionedProposal::V2(proposal));
+        }
+
+        testing_env!(get_context()
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(contract.storage_balance_bounds().min.0 * 10)
+            .build());
+        contract.storage_deposit(None, None);
+        let used_bytes = |contract: &Contract| {
+            contract.storage_accounts.get(&accounts(2).to_string()).unwrap().used_bytes
+        };
+        contract.act_proposal(1, Action::Vote { option_id: "yes".to_string() });
+        let initial_used_bytes = used_bytes(&contract);
+        contract.act_proposal(2, Action::Vote { option_id: "yes".to_string() });
+        let vote_used_bytes = used_bytes(&contract) - initial_used_bytes;
+
+        // Voting on the V1 proposal costs the same as on the upgraded ones.
+        contract.act_proposal(0, Action::Vote { option_id: "yes".to_string() });
+        assert!(vote_used_bytes > 0);
+        assert_eq!(used_bytes(&contract), initial_used_bytes + 2 * vote_used_bytes);
+    }
+
+    #[test]
+    fn test_v1_proposal_indexed_once_when_modified_before_batch() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(2);
+        let mut contract = Contract::migrate(None);
+
+        testing_env!(get_context()
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(contract.storage_balance_bounds().min.0 * 10)
+            .build());
+        contract.storage_deposit(None, None);
+        contract.act_proposal(1, Action::Vote { option_id: "yes".to_string() });
+        assert_eq!(contract.get_account_votes(accounts(1).to_string(), 0, 10).len(), 1);
+
+        testing_env!(get_context().build());
+        assert!(contract.migrate_indexes(10));
+        let voted: Vec<u64> = contract
+            .get_account_votes(accounts(1).to_string(), 0, 10)
+            .into_iter()
+            .map(|vote| vote.proposal_id)
+            .collect();
+        assert_eq!(voted, vec![1, 0]);
+        let active: Vec<u64> = contract
+            .get_filtered_proposals(active_filter(), 0, 10, None)
+            .into_iter()
+            .map(|proposal| proposal.id)
+            .collect();
+        assert_eq!(active, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_migrate_indexes_in_batches() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(5);
+        let mut contract = Contract::migrate(None);
+        assert_eq!(contract.pending_indexes, Some((0, 5)));
+
+        assert!(!contract.migrate_indexes(2));
+        assert_eq!(contract.get_account_votes(accounts(1).to_string(), 0, 10).len(), 2);
+        assert!(!contract.migrate_indexes(2));
+        assert!(contract.migrate_indexes(2));
+        assert_eq!(contract.pending_indexes, None);
+        assert_eq!(contract.get_account_votes(accounts(1).to_string(), 0, 10).len(), 5);
+        assert_eq!(contract.get_filtered_proposals(active_filter(), 0, 10, None).len(), 5);
+
+        assert!(contract.migrate_indexes(2));
+        assert_eq!(contract.get_account_votes(accounts(1).to_string(), 0, 10).len(), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "ONLY_OWNER")]
+    fn test_migrate_indexes_only_owner() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(1);
+        let mut contract = Contract::migrate(None);
+        testing_env!(get_context().predecessor_account_id(accounts(1)).build());
+        contract.migrate_indexes(10);
+    }
+
+    #[test]
+    fn test_migrate_from_v1_records_factory() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(0);
+        let contract = Contract::migrate(Some(accounts(5)));
+        assert_eq!(contract.factory_id, Some(accounts(5).to_string()));
+        env::state_write(&contract);
+
+        let contract = Contract::migrate(None);
+        assert_eq!(contract.factory_id, Some(accounts(5).to_string()));
+    }
+}
@@ -1,12 +1,22 @@
 use crate::*;
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub enum ProposalStatus {
     InProgress,
     Expired,
 }
 
+impl ProposalStatus {
+    // Returns label used to index proposals by status.
+    pub fn to_label(&self) -> &str {
+        match self {
+            ProposalStatus::InProgress => "in_progress",
+            ProposalStatus::Expired => "expired",
+        }
+    }
+}
+
 // Kinds of proposals, doing different action.
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(crate = "near_sdk::serde")]
@@ -43,14 +53,6 @@ pub struct VoteOption {
     pub min_vote_weight: Balance,
 }
 
-impl From<VersionedProposal> for Proposal {
-    fn from(v: VersionedProposal) -> Self {
-        match v {
-            VersionedProposal::Default(p) => p,
-        }
-    }
-}
-
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Vote {
@@ -59,8 +61,9 @@ pub struct Vote {
 }
 
 // Proposal that are sent to this DAO.
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
-#[serde(crate = "near_sdk::serde")]
+// Votes and donations live in their own collections under per-proposal storage prefixes,
+// so acting on a proposal only reads and writes the affected entries.
+#[derive(BorshSerialize, BorshDeserialize)]
 pub struct Proposal {
     // Original proposer.
     pub proposer: AccountId,
@@ -77,107 +80,196 @@ pub struct Proposal {
     pub submission_time: U64,
     pub duration: U64,
 
-    pub donations: HashMap<AccountId, Balance>,
+    pub donations: UnorderedMap<AccountId, Balance>,
+    // Donors ordered by donated amount.
+    pub donation_ranking: DonationRanking,
     pub total_donations: Balance,
 
+    pub total_delegation_amount: Balance,
+    pub votes: LookupMap<AccountId, Vote>,
+    pub option_delegations: HashMap<String, Balance>
+}
+
+// Proposal layout of state version 1, with votes and donations kept inline.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ProposalV1 {
+    pub proposer: AccountId,
+    pub description: String,
+    pub kind: ProposalKind,
+    pub status: ProposalStatus,
+    pub options: HashMap<String, VoteOption>,
+    pub submission_time: U64,
+    pub duration: U64,
+    pub donations: HashMap<AccountId, Balance>,
+    pub total_donations: Balance,
     pub total_delegation_amount: Balance,
     pub votes: HashMap<AccountId, Vote>,
     pub option_delegations: HashMap<String, Balance>
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
-#[serde(crate = "near_sdk::serde")]
+impl ProposalV1 {
+    // Returns timestamp at which the voting period ends.
+    pub fn end_time(&self) -> u64 {
+        self.submission_time.0 + self.duration.0
+    }
+
+    // Moves inline votes and donations into per-proposal collections.
+    pub fn upgrade(self, id: u64) -> Proposal {
+        let mut votes = LookupMap::new(StorageKeys::ProposalVotes { proposal_id: id });
+        for (account_id, vote) in self.votes.iter() {
+            votes.insert(account_id, vote);
+        }
+        let mut donations = UnorderedMap::new(StorageKeys::ProposalDonations { proposal_id: id });
+        let mut donation_ranking = TreeMap::new(StorageKeys::ProposalDonationRanking { proposal_id: id });
+        for (account_id, amount) in self.donations.iter() {
+            donations.insert(account_id, amount);
+            update_donation_ranking(&mut donation_ranking, account_id, 0, *amount);
+        }
+        Proposal {
+            proposer: self.proposer,
+            description: self.description,
+            kind: self.kind,
+            status: self.status,
+            options: self.options,
+            submission_time: self.submission_time,
+            duration: self.duration,
+            donations,
+            donation_ranking,
+            total_donations: self.total_donations,
+            total_delegation_amount: self.total_delegation_amount,
+            votes,
+            option_delegations: self.option_delegations
+        }
+    }
+}
+
+// Proposals stored in state version 1 stay `Default` until they are first acted on,
+// at which point they are upgraded and written back as `V2`.
+#[derive(BorshSerialize, BorshDeserialize)]
 pub enum VersionedProposal {
-    Default(Proposal),
+    Default(ProposalV1),
+    V2(Proposal),
+}
+
+impl VersionedProposal {
+    pub fn get_user_voted(&self, account_id: &AccountId) -> Option<Vote> {
+        match self {
+            VersionedProposal::Default(proposal) => proposal.votes.get(account_id).cloned(),
+            VersionedProposal::V2(proposal) => proposal.get_user_voted(account_id),
+        }
+    }
+
+    // Returns donors with the highest donations first, in paginated view.
+    pub fn top_donors(&self, from_index: u64, limit: u64) -> Vec<ProposalDonateAsObject> {
+        match self {
+            VersionedProposal::Default(proposal) => {
+                let mut donations: Vec<(&AccountId, &Balance)> = proposal.donations.iter().collect();
+                donations.sort_by(|a, b| b.1.cmp(a.1));
+                donations
+                    .into_iter()
+                    .skip(from_index as usize)
+                    .take(limit as usize)
+                    .map(|(account_id, balance)| ProposalDonateAsObject {
+                        account: account_id.clone(),
+                        doate_balance: *balance,
+                    })
+                    .collect()
+            }
+            VersionedProposal::V2(proposal) => top_donors(&proposal.donation_ranking, from_index, limit),
+        }
+    }
 }
 
 impl Proposal {
-    pub fn get_user_voted(&self, account_id: &AccountId) -> Option<&Vote> {
+    pub fn new(id: u64, input: ProposalInput) -> Self {
+        assert!(input.duration.0 > 1000000000 * 60 * 2, "DURATION_MIN_ERROR");
+        let options = match input.kind {
+            ProposalKind::Donate => HashMap::default(),
+            ProposalKind::Vote { .. } => input.options,
+        };
+        Self {
+            proposer: env::predecessor_account_id(),
+            description: input.description,
+            kind: input.kind,
+            options,
+            status: ProposalStatus::InProgress,
+            submission_time: U64::from(env::block_timestamp()),
+            donations: UnorderedMap::new(StorageKeys::ProposalDonations { proposal_id: id }),
+            donation_ranking: TreeMap::new(StorageKeys::ProposalDonationRanking { proposal_id: id }),
+            total_delegation_amount: 0,
+            total_donations: 0,
+            votes: LookupMap::new(StorageKeys::ProposalVotes { proposal_id: id }),
+            option_delegations: HashMap::default(),
+            duration: input.duration
+        }
+    }
+
+    pub fn get_user_voted(&self, account_id: &AccountId) -> Option<Vote> {
         self.votes.get(account_id)
     }
 
+    // Returns timestamp at which the voting period ends.
+    pub fn end_time(&self) -> u64 {
+        self.submission_time.0 + self.duration.0
+    }
+
     // add total delegations, add vote, add vote delegations
-    pub fn add_vote(&mut self, account_id: &AccountId, option_id: &String, vote_delegation: Balance, kind: &VoteKind) -> Self {
-        assert!(self.options.get(option_id).is_some(), "INVALID_OPTION_ID");
-        match kind {
-            VoteKind::VoteByDelegation => {
-                let vote = Vote {
-                    option: option_id.to_string(),
-                    delegations: vote_delegation
-                };
-                assert!(self.votes.insert(account_id.to_string(), vote).is_none(), "ERR_ALREADY_VOTED");
-                self.total_delegation_amount += vote_delegation;
-                let option_prev_delegation_amount = self.option_delegations.get(option_id).unwrap_or(&0);
-                let option_new_delegation_amount = option_prev_delegation_amount + vote_delegation;
-                self.option_delegations.insert(option_id.to_string(), option_new_delegation_amount);
-            },
-            VoteKind::MajorityVote => {
-                let vote = Vote {
-                    option: option_id.to_string(),
-                    delegations: 1
-                };
-                assert!(self.votes.insert(account_id.to_string(), vote).is_none(), "ERR_ALREADY_VOTED");
-                self.total_delegation_amount += 1;
-                let option_prev_delegation_amount = self.option_delegations.get(option_id).unwrap_or(&0);
-                let option_new_delegation_amount = option_prev_delegation_amount + 1;
-                self.option_delegations.insert(option_id.to_string(), option_new_delegation_amount);
-            },
-        }
-        self.clone()
+    pub fn add_vote(&mut self, account_id: &AccountId, option_id: &String, vote_delegation: Balance, kind: &VoteKind) {
+        assert!(self.options.contains_key(option_id), "INVALID_OPTION_ID");
+        let delegations = match kind {
+            VoteKind::VoteByDelegation => vote_delegation,
+            VoteKind::MajorityVote => 1,
+        };
+        let vote = Vote {
+            option: option_id.to_string(),
+            delegations
+        };
+        assert!(self.votes.insert(account_id, &vote).is_none(), "ERR_ALREADY_VOTED");
+        self.total_delegation_amount += delegations;
+        let option_prev_delegation_amount = self.option_delegations.get(option_id).unwrap_or(&0);
+        let option_new_delegation_amount = option_prev_delegation_amount + delegations;
+        self.option_delegations.insert(option_id.to_string(), option_new_delegation_amount);
     }
 
     // remove total delegations, remove vote, remove vote delegations
-    pub fn remove_vote(&mut self, account_id: &AccountId, kind: &VoteKind) {
+    pub fn remove_vote(&mut self, account_id: &AccountId) {
         let vote = self.votes.remove(account_id).expect("ERR_DID_NOT_VOTED");
-        match kind {
-            VoteKind::VoteByDelegation => {
-                self.total_delegation_amount -= vote.delegations;
-                let option_prev_delegation_amount = self.option_delegations.get(&vote.option).unwrap_or(&0);
-                let option_new_delegation_amount = option_prev_delegation_amount - vote.delegations;
-                self.option_delegations.insert(vote.option, option_new_delegation_amount);
-            },
-            VoteKind::MajorityVote => {
-                self.total_delegation_amount -= 1;
-                let option_prev_delegation_amount = self.option_delegations.get(&vote.option).unwrap_or(&0);
-                let option_new_delegation_amount = option_prev_delegation_amount - 1;
-                self.option_delegations.insert(vote.option, option_new_delegation_amount);
-            },
-        }
+        self.total_delegation_amount -= vote.delegations;
+        let option_prev_delegation_amount = self.option_delegations.get(&vote.option).unwrap_or(&0);
+        let option_new_delegation_amount = option_prev_delegation_amount - vote.delegations;
+        self.option_delegations.insert(vote.option, option_new_delegation_amount);
     }
 
-    pub fn donate(&mut self, account_id: &AccountId, amount: Balance) -> Self {
-        let prev_amount = self.donations.get(&account_id.to_string()).unwrap_or(&0).clone();
-        let new_amount = 0 + amount;
-        self.donations.insert(account_id.to_string(), new_amount);
+    pub fn donate(&mut self, account_id: &AccountId, amount: Balance) {
+        let prev_amount = self.donations.get(account_id).unwrap_or(0);
+        let new_amount = prev_amount + amount;
+        self.donations.insert(account_id, &new_amount);
+        update_donation_ranking(&mut self.donation_ranking, account_id, prev_amount, new_amount);
         self.total_donations += amount;
-        self.clone()
     } 
 
-    // Adds vote of the given user with given `amount` of weight. If user already voted, fails.
+    // Adds vote of the given user with given `amount` of weight. Replaces previous vote of the user.
     pub fn update_votes(
         &mut self,
         account_id: &AccountId,
         option_id: &String,
         delegation_amount: Balance 
-    ) -> Self {
+    ) {
         let proposal_kind = self.kind.clone();
-        let mut new_proposal = self.clone();
         match proposal_kind {
             ProposalKind::Vote { vote_kind } => {
-                if self.votes.get(account_id).is_some() {
-                    self.remove_vote(account_id, &vote_kind);
+                if self.votes.contains_key(account_id) {
+                    self.remove_vote(account_id);
                 };
-                new_proposal = self.add_vote(account_id, option_id, delegation_amount, &vote_kind);
+                self.add_vote(account_id, option_id, delegation_amount, &vote_kind);
             },
             _ => unreachable!(),
         };
-        new_proposal
     }
 
     pub fn update_status(&mut self, status: ProposalStatus) {
         self.status = status;
     }
-
 }
 
 #[derive(Serialize, Deserialize)]
@@ -191,44 +283,77 @@ pub struct ProposalInput {
     pub options: HashMap<String, VoteOption>,
 }
 
-impl From<ProposalInput> for Proposal {
-    fn from(input: ProposalInput) -> Self {
-        assert!(input.duration.0 > 1000000000 * 60 * 2, "DURATION_MIN_ERROR");
-        match input.kind {
-            ProposalKind::Donate => {
-                Self {
-                    proposer: env::predecessor_account_id(),
-                    description: input.description,
-                    kind: input.kind,
-                    options: HashMap::default(),
-                    status: ProposalStatus::InProgress,
-                    submission_time: U64::from(env::block_timestamp()),
-                    donations: HashMap::default(),
-                    total_delegation_amount: 0,
-                    total_donations: 0,
-                    votes: HashMap::default(),
-                    option_delegations: HashMap::default(),
-                    duration: input.duration
+impl Contract {
+    // Returns proposal in the current layout, upgrading it if it was stored by an older version
+    // and indexing it if `migrate_indexes` has not reached it yet.
+    // Upgraded proposals are written back right away, so the storage the upgrade moves isn't
+    // charged to the account acting on the proposal. Caller is responsible for writing back changes.
+    pub(crate) fn internal_get_proposal(&mut self, id: u64) -> Proposal {
+        match self.proposals.get(&id).expect("ERR_NO_PROPOSAL") {
+            VersionedProposal::Default(proposal) => {
+                if self.is_index_pending(id) {
+                    self.internal_index_v1_proposal(id, &proposal);
                 }
+                self.proposals.insert(&id, &VersionedProposal::V2(proposal.upgrade(id)));
+                self.internal_get_proposal(id)
             }
-            ProposalKind::Vote { .. } => {
-                Self {
-                    proposer: env::predecessor_account_id(),
-                    description: input.description,
-                    kind: input.kind,
-                    options: input.options,
-                    status: ProposalStatus::InProgress,
-                    submission_time: U64::from(env::block_timestamp()),
-                    donations: HashMap::default(),
-                    total_delegation_amount: 0,
-                    total_donations: 0,
-                    votes: HashMap::default(),
-                    option_delegations: HashMap::default(),
-                    duration: input.duration
-                }
-            },
-        } 
-        
+            VersionedProposal::V2(proposal) => proposal,
+        }
+    }
+
+    // Adds proposal id to the status, kind and proposer indexes.
+    pub(crate) fn internal_index_proposal(&mut self, id: u64, end_time: u64, status: &ProposalStatus, kind: &ProposalKind, proposer: &AccountId) {
+        self.internal_add_to_status_index(id, end_time, status);
+
+        let kind = kind.to_policy_label().to_string();
+        let mut kind_ids = self.proposals_by_kind.get(&kind).unwrap_or_else(|| {
+            Vector::new(StorageKeys::ProposalKindIndex { kind: kind.clone() })
+        });
+        kind_ids.push(&id);
+        self.proposals_by_kind.insert(&kind, &kind_ids);
+
+        let mut proposer_ids = self.proposals_by_proposer.get(proposer).unwrap_or_else(|| {
+            Vector::new(StorageKeys::ProposalProposerIndex { account_id: proposer.clone() })
+        });
+        proposer_ids.push(&id);
+        self.proposals_by_proposer.insert(proposer, &proposer_ids);
+    }
+
+    // Moves proposal id from the index of the previous status to the new one.
+    pub(crate) fn internal_update_status_index(&mut self, id: u64, end_time: u64, prev_status: &ProposalStatus, new_status: &ProposalStatus) {
+        if prev_status == new_status {
+            return;
+        }
+        let prev = prev_status.to_label().to_string();
+        if let Some(mut status_ids) = self.proposals_by_status.get(&prev) {
+            status_ids.remove(&id);
+            self.proposals_by_status.insert(&prev, &status_ids);
+        }
+        if prev_status == &ProposalStatus::InProgress {
+            self.active_proposals.remove(&(end_time, id));
+        }
+        self.internal_add_to_status_index(id, end_time, new_status);
+    }
+
+    // Records that account voted on given proposal.
+    pub(crate) fn internal_add_account_vote(&mut self, account_id: &AccountId, id: u64) {
+        let mut voted_ids = self.account_votes.get(account_id).unwrap_or_else(|| {
+            Vector::new(StorageKeys::AccountVotesIndex { account_id: account_id.clone() })
+        });
+        voted_ids.push(&id);
+        self.account_votes.insert(account_id, &voted_ids);
+    }
+
+    fn internal_add_to_status_index(&mut self, id: u64, end_time: u64, status: &ProposalStatus) {
+        let label = status.to_label().to_string();
+        let mut status_ids = self.proposals_by_status.get(&label).unwrap_or_else(|| {
+            TreeMap::new(StorageKeys::ProposalStatusIndex { status: label.clone() })
+        });
+        status_ids.insert(&id, &());
+        self.proposals_by_status.insert(&label, &status_ids);
+        if status == &ProposalStatus::InProgress {
+            self.active_proposals.insert(&(end_time, id), &());
+        }
     }
 }
 
@@ -243,39 +368,44 @@ impl Contract {
             "ONLY_OWNER"
         );
         // 1. Validate proposal.
-        let  proposal = Proposal::from(proposal_input);
+        let id = self.last_proposal_id;
+        let proposal = Proposal::new(id, proposal_input);
 
         // 3. Actually add proposal to the current list of proposals.
-        let id = self.last_proposal_id;
+        self.internal_index_proposal(id, proposal.end_time(), &proposal.status, &proposal.kind, &proposal.proposer);
         self.proposals
-            .insert(&id, &VersionedProposal::Default(proposal.into()));
+            .insert(&id, &VersionedProposal::V2(proposal));
         self.last_proposal_id += 1;
         id
     }
 
     pub fn act_proposal(&mut self, id: u64, action: Action) {
         let account_id = env::predecessor_account_id();
-        let mut proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
+        let mut proposal = self.internal_get_proposal(id);
         let user_delegate = self.delegations.get(&account_id).expect("USER_NOT_REGISTERED");
         assert!(user_delegate > 0, "USER_ZERO_DELEGATION");
-        {
-            let proposal_end_time_stamp = proposal.submission_time.0 + proposal.duration.0;
-            let current_block_timestamp = env::block_timestamp();
-            assert!(proposal_end_time_stamp > current_block_timestamp, "PROPOSAL_EXPIRED");
-        }
+        assert!(proposal.end_time() > env::block_timestamp(), "PROPOSAL_EXPIRED");
         match action {
             Action::Vote { option_id } => {
-                match &proposal.clone().kind {
-                    ProposalKind::Vote { vote_kind } => {
-                        let new_proposal = proposal.update_votes(&account_id, &option_id, user_delegate);
-                        self.proposals.insert(&id , &VersionedProposal::Default(new_proposal.into()));
+                match &proposal.kind {
+                    ProposalKind::Vote { .. } => {
+                        let initial_storage_usage = env::storage_usage();
+                        if proposal.get_user_voted(&account_id).is_none() {
+                            self.internal_add_account_vote(&account_id, id);
+                        }
+                        proposal.update_votes(&account_id, &option_id, user_delegate);
+                        self.proposals.insert(&id , &VersionedProposal::V2(proposal));
+                        self.internal_charge_storage(&account_id, initial_storage_usage);
                     },
-                    _ => unreachable!()
+                    _ => env::panic(b"ERR_WRONG_PROPOSAL_KIND"),
                 }
             },
             Action::Finalize => {
                 assert_eq!(account_id, self.owner_id, "ONLY_OWNER");
+                let prev_status = proposal.status.clone();
                 proposal.update_status(ProposalStatus::Expired);
+                self.internal_update_status_index(id, proposal.end_time(), &prev_status, &proposal.status);
+                self.proposals.insert(&id, &VersionedProposal::V2(proposal));
             }
         }
     }
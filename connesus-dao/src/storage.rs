@@ -0,0 +1,160 @@
+use crate::*;
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+
+// Storage staked by an account to pay for its votes, donations and delegation entries.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct StorageAccount {
+    // Total $NEAR deposited, including the registration cost.
+    pub deposit: Balance,
+    // Bytes of contract state used on behalf of this account.
+    pub used_bytes: StorageUsage,
+}
+
+impl StorageAccount {
+    pub fn locked_amount(&self, registration_cost: Balance) -> Balance {
+        registration_cost + Balance::from(self.used_bytes) * env::storage_byte_cost()
+    }
+
+    pub fn available_amount(&self, registration_cost: Balance) -> Balance {
+        self.deposit.saturating_sub(self.locked_amount(registration_cost))
+    }
+}
+
+impl Contract {
+    // Measures storage cost of registering the longest possible account id.
+    pub(crate) fn measure_storage_balance_cost(storage_accounts: &mut LookupMap<AccountId, StorageAccount>) -> Balance {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id = "a".repeat(64);
+        storage_accounts.insert(&tmp_account_id, &StorageAccount { deposit: 0, used_bytes: 0 });
+        let storage_balance_cost =
+            Balance::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost();
+        storage_accounts.remove(&tmp_account_id);
+        storage_balance_cost
+    }
+
+    pub(crate) fn internal_storage_deposit(&mut self, account_id: &AccountId, amount: Balance) {
+        let account = match self.storage_accounts.get(account_id) {
+            Some(mut account) => {
+                account.deposit += amount;
+                account
+            }
+            None => {
+                assert!(amount >= self.storage_balance_cost, "ERR_DEPOSIT_TOO_LOW");
+                StorageAccount { deposit: amount, used_bytes: 0 }
+            }
+        };
+        self.storage_accounts.insert(account_id, &account);
+    }
+
+    // Charges storage used since `initial_storage_usage` to given account, or releases it if usage went down.
+    // Panics if account's storage deposit doesn't cover the new usage.
+    pub(crate) fn internal_charge_storage(&mut self, account_id: &AccountId, initial_storage_usage: StorageUsage) {
+        let mut account = self
+            .storage_accounts
+            .get(account_id)
+            .expect("ERR_STORAGE_NOT_REGISTERED");
+        let storage_usage = env::storage_usage();
+        if storage_usage > initial_storage_usage {
+            account.used_bytes += storage_usage - initial_storage_usage;
+        } else {
+            account.used_bytes = account
+                .used_bytes
+                .saturating_sub(initial_storage_usage - storage_usage);
+        }
+        assert!(
+            account.deposit >= account.locked_amount(self.storage_balance_cost),
+            "ERR_NOT_ENOUGH_STORAGE_BALANCE"
+        );
+        self.storage_accounts.insert(account_id, &account);
+    }
+
+    fn internal_storage_balance_of(&self, account_id: &AccountId) -> Option<StorageBalance> {
+        self.storage_accounts.get(account_id).map(|account| StorageBalance {
+            total: account.deposit.into(),
+            available: account.available_amount(self.storage_balance_cost).into(),
+        })
+    }
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id
+            .map(|account_id| account_id.into())
+            .unwrap_or_else(env::predecessor_account_id);
+        let registration_only = registration_only.unwrap_or(false);
+        if self.storage_accounts.contains_key(&account_id) {
+            if registration_only {
+                if amount > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(amount);
+                }
+            } else {
+                self.internal_storage_deposit(&account_id, amount);
+            }
+        } else if registration_only {
+            assert!(amount >= self.storage_balance_cost, "ERR_DEPOSIT_TOO_LOW");
+            self.internal_storage_deposit(&account_id, self.storage_balance_cost);
+            let refund = amount - self.storage_balance_cost;
+            if refund > 0 {
+                Promise::new(env::predecessor_account_id()).transfer(refund);
+            }
+        } else {
+            self.internal_storage_deposit(&account_id, amount);
+        }
+        self.internal_storage_balance_of(&account_id).unwrap()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut account = self
+            .storage_accounts
+            .get(&account_id)
+            .expect("ERR_STORAGE_NOT_REGISTERED");
+        let available = account.available_amount(self.storage_balance_cost);
+        let amount = amount.map(|amount| amount.0).unwrap_or(available);
+        assert!(amount <= available, "ERR_NOT_ENOUGH_STORAGE_BALANCE");
+        account.deposit -= amount;
+        self.storage_accounts.insert(&account_id, &account);
+        if amount > 0 {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+        self.internal_storage_balance_of(&account_id).unwrap()
+    }
+
+    // Votes and donations can't be removed, so only accounts that never used storage can unregister.
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        assert!(!force.unwrap_or(false), "ERR_FORCE_UNREGISTER_NOT_SUPPORTED");
+        let account_id = env::predecessor_account_id();
+        if let Some(account) = self.storage_accounts.get(&account_id) {
+            assert_eq!(account.used_bytes, 0, "ERR_STORAGE_IN_USE");
+            self.storage_accounts.remove(&account_id);
+            Promise::new(account_id).transfer(account.deposit);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: self.storage_balance_cost.into(),
+            max: None,
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
+        self.internal_storage_balance_of(account_id.as_ref())
+    }
+}
@@ -0,0 +1,102 @@
+use crate::*;
+
+// Gas for migrating state after new code is deployed.
+const GAS_FOR_MIGRATE: Gas = 50_000_000_000_000;
+
+// Gas for syncing metadata to the factory.
+const GAS_FOR_UPDATE_DAO_METADATA: Gas = 10_000_000_000_000;
+
+// Gas for applying metadata once the factory has it.
+const GAS_FOR_ON_UPDATE_METADATA: Gas = 10_000_000_000_000;
+
+#[ext_contract(ext_factory)]
+pub trait ExtDaoFactory {
+    fn update_dao_metadata(&mut self, metadata: DaoMetadata);
+}
+
+#[ext_contract(ext_self)]
+pub trait ExtSelf {
+    fn on_update_metadata(&mut self, metadata: DaoMetadata, account_id: AccountId, deposit: U128) -> bool;
+}
+
+impl Contract {
+    // Asserts that the call comes from the factory that created this DAO.
+    fn assert_factory(&self) {
+        assert!(
+            self.factory_id.as_ref() == Some(&env::predecessor_account_id()),
+            "ERR_NOT_ALLOWED"
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Deploys code passed by the factory as raw input and migrates state to it.
+    // The factory is already recorded, so `migrate` gets no `factory_id`.
+    // Called by the factory on behalf of the DAO owner, see `DaoFactory::upgrade_dao`.
+    pub fn update_from_factory(&self) -> Promise {
+        self.assert_factory();
+        let code = env::input().expect("ERR_NO_INPUT");
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(b"migrate".to_vec(), b"{}".to_vec(), 0, GAS_FOR_MIGRATE)
+    }
+
+    // Sets new owner of the DAO. Called by the factory on behalf of the current owner,
+    // see `DaoFactory::transfer_dao_ownership`, so the factory registry stays in sync.
+    pub fn set_owner_from_factory(&mut self, new_owner_id: ValidAccountId) {
+        self.assert_factory();
+        self.owner_id = new_owner_id.into();
+    }
+
+    // Updates DAO metadata and the copy kept by the factory, if any. Symbol can't change as it names the DAO account.
+    // Attached deposit is forwarded to the factory to pay for the extra storage, the rest goes back to the owner.
+    // With a factory, metadata only changes once the factory accepted it, see `on_update_metadata`.
+    // Without one the deposit is refunded.
+    #[payable]
+    pub fn update_metadata(&mut self, metadata: DaoMetadata) -> Promise {
+        let account_id = env::predecessor_account_id();
+        assert_eq!(account_id, self.owner_id, "ONLY_OWNER");
+        metadata.assert_valid();
+        assert_eq!(metadata.symbol, self.dao_metadata.symbol, "ERR_SYMBOL_CANNOT_CHANGE");
+        let deposit = env::attached_deposit();
+        match &self.factory_id {
+            Some(factory_id) => ext_factory::update_dao_metadata(
+                metadata.clone(),
+                factory_id,
+                deposit,
+                GAS_FOR_UPDATE_DAO_METADATA,
+            )
+            .then(ext_self::on_update_metadata(
+                metadata,
+                account_id,
+                U128(deposit),
+                &env::current_account_id(),
+                0,
+                GAS_FOR_ON_UPDATE_METADATA,
+            )),
+            None => {
+                self.dao_metadata = metadata;
+                Promise::new(account_id).transfer(deposit)
+            }
+        }
+    }
+
+    // Applies metadata accepted by the factory. If the factory rejected it, the deposit
+    // came back to the DAO and is refunded to the caller of `update_metadata`.
+    #[private]
+    pub fn on_update_metadata(&mut self, metadata: DaoMetadata, account_id: AccountId, deposit: U128) -> bool {
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                self.dao_metadata = metadata;
+                true
+            }
+            _ => {
+                if deposit.0 > 0 {
+                    Promise::new(account_id).transfer(deposit.0);
+                }
+                false
+            }
+        }
+    }
+}
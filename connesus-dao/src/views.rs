@@ -1,6 +1,5 @@
-use std::iter::FromIterator;
-
 use crate::*;
+use std::ops::Bound;
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
@@ -19,32 +18,31 @@ pub struct ProposalBaseInformation {
 
 impl From<VersionedProposal> for ProposalBaseInformation {
     fn from(proposal: VersionedProposal) -> Self {
-        let Proposal {
-            proposer,
-            description,
-            kind,
-            options,
-            status,
-            submission_time,
-            total_delegation_amount,
-            total_donations,
-            option_delegations,
-            duration,
-            donations: _,
-            votes: _
-        } = proposal.into();
-
-        Self {
-            proposer,
-            description,
-            kind,
-            options,
-            status,
-            submission_time,
-            total_delegation_amount,
-            total_donations,
-            option_delegations,
-            duration
+        match proposal {
+            VersionedProposal::Default(proposal) => Self {
+                proposer: proposal.proposer,
+                description: proposal.description,
+                kind: proposal.kind,
+                options: proposal.options,
+                status: proposal.status,
+                submission_time: proposal.submission_time,
+                total_delegation_amount: proposal.total_delegation_amount,
+                total_donations: proposal.total_donations,
+                option_delegations: proposal.option_delegations,
+                duration: proposal.duration
+            },
+            VersionedProposal::V2(proposal) => Self {
+                proposer: proposal.proposer,
+                description: proposal.description,
+                kind: proposal.kind,
+                options: proposal.options,
+                status: proposal.status,
+                submission_time: proposal.submission_time,
+                total_delegation_amount: proposal.total_delegation_amount,
+                total_donations: proposal.total_donations,
+                option_delegations: proposal.option_delegations,
+                duration: proposal.duration
+            },
         }
     }
 }
@@ -74,23 +72,23 @@ pub struct BountyBaseInformation {
 
 impl From<VersionedBounty> for BountyBaseInformation {
     fn from(bounty: VersionedBounty) -> Self {
-        let Bounty {
-            description,
-            token,
-            total,
-            rest,
-            start_time,
-            duration,
-            claimer: _
-        } = bounty.into();
-
-        Self {
-            description,
-            token,
-            total,
-            rest,
-            start_time,
-            duration,
+        match bounty {
+            VersionedBounty::Default(bounty) => Self {
+                description: bounty.description,
+                token: bounty.token,
+                total: bounty.total,
+                rest: bounty.rest,
+                start_time: bounty.start_time,
+                duration: bounty.duration,
+            },
+            VersionedBounty::V2(bounty) => Self {
+                description: bounty.description,
+                token: bounty.token,
+                total: bounty.total,
+                rest: bounty.rest,
+                start_time: bounty.start_time,
+                duration: bounty.duration,
+            },
         }
     }
 }
@@ -106,6 +104,27 @@ pub struct BountyOutput {
     pub bounty: BountyBaseInformation,
 }
 
+// This is format of output via JSON for the account's vote on a proposal.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountVoteOutput {
+    pub proposal_id: u64,
+    pub vote: Vote,
+    pub status: ProposalStatus,
+}
+
+// Criteria for filtered proposal listing. Unset fields match any proposal.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProposalFilter {
+    pub status: Option<ProposalStatus>,
+    // Label of proposal kind, e.g. "donate" or "vote".
+    pub kind: Option<String>,
+    pub proposer: Option<AccountId>,
+    // Only proposals in progress whose voting period has not ended.
+    pub active_only: Option<bool>,
+}
+
 // This is format of output via JSON for the proposal.
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
@@ -114,6 +133,20 @@ pub struct ProposalDonateAsObject {
     pub doate_balance: Balance
 }
 
+fn proposal_output(id: u64, proposal: VersionedProposal, account_id: Option<&AccountId>) -> ProposalOutput {
+    let voted = account_id
+        .and_then(|account_id| proposal.get_user_voted(account_id))
+        .unwrap_or(Vote {
+            option: "_".to_string(),
+            delegations: 0
+        });
+    ProposalOutput {
+        id,
+        proposal: ProposalBaseInformation::from(proposal),
+        user_select: voted,
+    }
+}
+
 #[near_bindgen]
 impl Contract {
     // Returns semver of this contract.
@@ -179,74 +212,136 @@ impl Contract {
     }
 
     // Get proposals in paginated view.
-    pub fn get_proposals(&self, from_index: u64, limit: u64, account_id: AccountId) -> Vec<ProposalOutput> {
+    pub fn get_proposals(&self, from_index: u64, limit: u64, account_id: Option<AccountId>) -> Vec<ProposalOutput> {
         (from_index..std::cmp::min(self.last_proposal_id, from_index + limit))
             .filter_map(|id| {
                 self.proposals.get(&id).map(|versioned_proposal| {
-                    let proposal = Proposal::from(versioned_proposal.clone());
-                    let voted = if proposal.votes.get(&account_id).is_some() {
-                        proposal.votes.get(&account_id).unwrap().clone()
-                    } else {
-                        Vote {
-                            option: "_".to_string(),
-                            delegations: 0
-                        }
-                    };
-                    ProposalOutput {
-                        id,
-                        proposal: ProposalBaseInformation::from(versioned_proposal.clone()),
-                        user_select: voted,
-                    }
+                    proposal_output(id, versioned_proposal, account_id.as_ref())
                 })
             })
             .collect()
     }
 
     // Get specific proposal.
-    pub fn get_proposal(&self, id: u64, account_id: AccountId) -> Option<ProposalOutput> {
-        let versioned_proposal = self.proposals.get(&id);
-        let output = if versioned_proposal.clone().is_some() {
-            let proposal: Proposal = Proposal::from(versioned_proposal.clone().unwrap());
-            let voted = if proposal.votes.get(&account_id).is_some() {
-                proposal.votes.get(&account_id).unwrap().clone()
-            } else {
-                Vote {
-                    option: "_".to_string(),
-                    delegations: 0
-                }
-            };
-            Some(ProposalOutput {
-                id,
-                proposal: ProposalBaseInformation::from(versioned_proposal.unwrap().clone()),
-                user_select: voted,
-            })
+    pub fn get_proposal(&self, id: u64, account_id: Option<AccountId>) -> Option<ProposalOutput> {
+        self.proposals
+            .get(&id)
+            .map(|versioned_proposal| proposal_output(id, versioned_proposal, account_id.as_ref()))
+    }
+
+    // Get proposals matching given filter in paginated view.
+    // Active proposals are listed from the in-progress index, soonest to end first, skipping
+    // those whose voting period has ended. Otherwise proposals are listed in creation order,
+    // reading candidates lazily from the shortest index among the filtered fields.
+    pub fn get_filtered_proposals(
+        &self,
+        filter: ProposalFilter,
+        from_index: u64,
+        limit: u64,
+        account_id: Option<AccountId>,
+    ) -> Vec<ProposalOutput> {
+        let active_only = filter.active_only.unwrap_or(false);
+        let status = if active_only {
+            Some(ProposalStatus::InProgress)
         } else {
-            None   
+            filter.status.clone()
+        };
+        // A filter on a value that was never indexed matches nothing.
+        let status_ids = match &status {
+            Some(status) => match self.proposals_by_status.get(&status.to_label().to_string()) {
+                Some(ids) => Some(ids),
+                None => return vec![],
+            },
+            None => None,
+        };
+        let kind_ids = match &filter.kind {
+            Some(kind) => match self.proposals_by_kind.get(kind) {
+                Some(ids) => Some(ids),
+                None => return vec![],
+            },
+            None => None,
+        };
+        let proposer_ids = match &filter.proposer {
+            Some(proposer) => match self.proposals_by_proposer.get(proposer) {
+                Some(ids) => Some(ids),
+                None => return vec![],
+            },
+            None => None,
         };
-        output
-    }
 
-    pub fn get_proposal_donation(&self, id: u64, from_index: usize, limit: usize) -> Vec<ProposalDonateAsObject> {
-        let proposal: Proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL").into();
-        let donations = proposal.donations.clone();
-        let mut hash_vec = Vec::from_iter(donations.into_iter());
-        hash_vec.sort_by(|a, b| b.1.cmp(&a.1));
-        let donations_slice = &hash_vec[from_index..std::cmp::min(from_index + limit, hash_vec.len())];
-        let response: Vec<ProposalDonateAsObject> = donations_slice.into_iter().map(|(account_id, balance)| {
-            ProposalDonateAsObject {
-                account: account_id.clone(),
-                doate_balance: balance.clone(),
+        let candidate_ids: Box<dyn Iterator<Item = u64> + '_> = if active_only {
+            Box::new(
+                self.active_proposals
+                    .range((Bound::Excluded((env::block_timestamp(), u64::MAX)), Bound::Unbounded))
+                    .map(|((_, id), _)| id),
+            )
+        } else {
+            let mut indexes: Vec<(u64, Box<dyn Iterator<Item = u64> + '_>)> =
+                vec![(self.last_proposal_id, Box::new(0..self.last_proposal_id))];
+            if let Some(ids) = &status_ids {
+                indexes.push((ids.len(), Box::new(ids.iter().map(|(id, _)| id))));
             }
-        }).collect();
-        response
+            if let Some(ids) = &kind_ids {
+                indexes.push((ids.len(), Box::new(ids.iter())));
+            }
+            if let Some(ids) = &proposer_ids {
+                indexes.push((ids.len(), Box::new(ids.iter())));
+            }
+            indexes.into_iter().min_by_key(|(len, _)| *len).unwrap().1
+        };
+
+        candidate_ids
+            .filter_map(|id| self.proposals.get(&id).map(|versioned_proposal| (id, versioned_proposal)))
+            .map(|(id, versioned_proposal)| proposal_output(id, versioned_proposal, account_id.as_ref()))
+            .filter(|output| {
+                let proposal = &output.proposal;
+                status.iter().all(|status| &proposal.status == status)
+                    && filter.kind.iter().all(|kind| proposal.kind.to_policy_label() == kind)
+                    && filter.proposer.iter().all(|proposer| &proposal.proposer == proposer)
+            })
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .collect()
+    }
+
+    // Get proposals given account has voted on, with its vote, in paginated view.
+    pub fn get_account_votes(&self, account_id: AccountId, from_index: u64, limit: u64) -> Vec<AccountVoteOutput> {
+        let voted_ids = match self.account_votes.get(&account_id) {
+            Some(voted_ids) => voted_ids,
+            None => return vec![],
+        };
+        (from_index..std::cmp::min(voted_ids.len(), from_index.saturating_add(limit)))
+            .filter_map(|index| {
+                let proposal_id = voted_ids.get(index)?;
+                let proposal = self.proposals.get(&proposal_id)?;
+                let vote = proposal.get_user_voted(&account_id)?;
+                Some(AccountVoteOutput {
+                    proposal_id,
+                    vote,
+                    status: ProposalBaseInformation::from(proposal).status,
+                })
+            })
+            .collect()
+    }
+
+    pub fn get_proposal_donation(&self, id: u64, from_index: u64, limit: u64) -> Vec<ProposalDonateAsObject> {
+        let proposal = self.proposals.get(&id).expect("ERR_NO_PROPOSAL");
+        proposal.top_donors(from_index, limit)
+    }
+
+    // Get donors with the highest donations to given proposal, or to the DAO itself if no proposal given.
+    pub fn get_top_donors(&self, proposal_id: Option<u64>, from_index: u64, limit: u64) -> Vec<ProposalDonateAsObject> {
+        match proposal_id {
+            Some(id) => self.get_proposal_donation(id, from_index, limit),
+            None => top_donors(&self.open_donation_ranking, from_index, limit),
+        }
     }
 
     pub fn get_bounties(&self, from_index: u64, limit: u64, account_id: AccountId) -> Vec<BountyOutput> {
         (from_index..std::cmp::min(self.last_bounty_id, from_index + limit))
             .filter_map(|id| {
                 self.bounties.get(&id).map(|versioned_bounty| {
-                    let bounty: Bounty = versioned_bounty.clone().into();
-                    let claim_value = bounty.claimer.get(&account_id.to_string()).unwrap_or(&0u128).clone();
+                    let claim_value = versioned_bounty.get_claim_amount(&account_id);
                     BountyOutput {
                         id,
                         claim_amount: claim_value,
@@ -258,25 +353,16 @@ impl Contract {
     }
 
     pub fn get_bounty(&self, id: u64, account_id: Option<AccountId>) -> Option<BountyOutput> {
-        let versioned_bounty_option = self.bounties.get(&id);
-        let output = if versioned_bounty_option.is_some() {
-            let versioned_bounty_unwrapped = versioned_bounty_option.unwrap();
-            let claim_value = if account_id.is_some() {
-                let bounty: Bounty = versioned_bounty_unwrapped.clone().into();
-                let claim_value = bounty.claimer.get(&account_id.unwrap()).unwrap_or(&0u128).clone();
-                claim_value
-            } else {
-                0
-            }; 
-            let bounty_output = BountyOutput {
+        self.bounties.get(&id).map(|versioned_bounty| {
+            let claim_value = match account_id {
+                Some(account_id) => versioned_bounty.get_claim_amount(&account_id),
+                None => 0,
+            };
+            BountyOutput {
                 id,
                 claim_amount: claim_value,
-                bounty: BountyBaseInformation::from(versioned_bounty_unwrapped)
-            };
-            Some(bounty_output)
-        } else {
-            None
-        };
-        output
+                bounty: BountyBaseInformation::from(versioned_bounty)
+            }
+        })
     }
 }
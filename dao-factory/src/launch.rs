@@ -0,0 +1,185 @@
+use crate::*;
+use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+use near_sdk::PromiseOrValue;
+
+// Gas for the callback that continues with the DAO once its token is created.
+// Covers the DAO deployment and its own callback.
+const GAS_FOR_ON_CREATE_TOKEN: Gas = 90_000_000_000_000;
+
+#[ext_contract(ext_self_launch)]
+pub trait ExtDaoFactoryLaunch {
+    fn on_create_token(
+        &mut self,
+        dao_id: DaoId,
+        account_id: AccountId,
+        dao_balance: U128,
+        token_balance: U128,
+    ) -> PromiseOrValue<bool>;
+}
+
+// Community token of a DAO launched with `create_dao_with_token` lives next to the DAO.
+pub fn token_account_id(dao_id: &DaoId) -> AccountId {
+    format!("{}-token.{}", dao_id, env::current_account_id())
+}
+
+fn get_token_min_attached_balance(code_len: usize) -> Balance {
+    (code_len + EXTRA_BYTES) as Balance * STORAGE_PRICE_PER_BYTE
+}
+
+#[near_bindgen]
+impl DaoFactory {
+    // Stores community token code passed as raw input, replacing the previous one.
+    // Only the factory owner can upload code. Attached deposit must cover the storage used, the rest is refunded.
+    #[payable]
+    pub fn store_token_code(&mut self) -> Base58CryptoHash {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only owner can store code"
+        );
+        let code = env::input().expect("No code attached");
+        let mut code_hash = CryptoHash::default();
+        code_hash.copy_from_slice(&env::sha256(&code));
+        let initial_storage_usage = env::storage_usage();
+        self.token_code.set(&code);
+        self.token_code_size = code.len() as u64;
+        let storage_cost = Balance::from(env::storage_usage().saturating_sub(initial_storage_usage))
+            * STORAGE_PRICE_PER_BYTE;
+        let deposit = env::attached_deposit();
+        assert!(deposit >= storage_cost, "Deposit is too low, required {}", storage_cost);
+        if deposit > storage_cost {
+            Promise::new(env::predecessor_account_id()).transfer(deposit - storage_cost);
+        }
+        code_hash.into()
+    }
+
+    pub fn get_required_deposit_with_token(&self, metadata: DaoMetadata, account_id: ValidAccountId) -> U128 {
+        let dao_id = metadata.symbol.to_ascii_lowercase();
+        let args = DaoArgs {
+            token_contract_id: token_account_id(&dao_id),
+            metadata,
+            owner_id: account_id.to_string(),
+            code_hash: self.default_code_hash.map(|code_hash| code_hash.into()),
+        };
+        assert!(self.token_code.is_some(), "No token code");
+        let (_, code_size) = self.internal_get_code_size(None);
+        let args_deposit = self.get_min_attached_balance(&args, code_size)
+            + get_token_min_attached_balance(self.token_code_size as usize);
+        if let Some(previous_balance) = self.storage_deposits.get(account_id.as_ref()) {
+            args_deposit.saturating_sub(previous_balance).into()
+        } else {
+            (self.storage_balance_cost + args_deposit).into()
+        }
+    }
+
+    // Launches a community in one transaction: deploys the community token owned by the caller,
+    // then a DAO on that token. Both are registered right away and the token is allowed in the factory.
+    // If the token fails everything is undone; if only the DAO fails, the token is kept so the DAO
+    // can be created again with `create_dao`.
+    #[payable]
+    pub fn create_dao_with_token(
+        &mut self,
+        metadata: DaoMetadata,
+        token_metadata: FungibleTokenMetadata,
+        total_supply: U128,
+    ) -> Promise {
+        self.assert_daos_migrated();
+        if env::attached_deposit() > 0 {
+            self.internal_storage_deposit(&env::predecessor_account_id(), env::attached_deposit());
+        }
+        metadata.assert_valid();
+        token_metadata.assert_valid();
+        let (dao_id, dao_account_id) = self.internal_dao_account_id(&metadata);
+        let token_account_id = token_account_id(&dao_id);
+        assert!(
+            env::is_valid_account_id(token_account_id.as_bytes()),
+            "Token Account ID is invalid"
+        );
+        let account_id = env::predecessor_account_id();
+
+        let token_code = self.token_code.get().expect("No token code");
+        let (code_hash, code_size) = self.internal_get_code_size(None);
+        let args = DaoArgs {
+            token_contract_id: token_account_id.clone(),
+            metadata,
+            owner_id: account_id.clone(),
+            code_hash: Some(code_hash.into()),
+        };
+        let dao_balance = self.get_min_attached_balance(&args, code_size);
+        let token_balance = get_token_min_attached_balance(self.token_code_size as usize);
+        self.internal_withdraw_deposit(&account_id, dao_balance + token_balance);
+
+        // Storage used by the registry is paid from the token balance, so the DAO gets its full
+        // balance. If only the DAO fails, `on_create` refunds that balance and the storage released
+        // by its registration.
+        let initial_storage_usage = env::storage_usage();
+        assert!(
+            self.allowed_tokens.insert(&token_account_id),
+            "Token ID is already taken {}", token_account_id
+        );
+        assert!(
+            self.internal_add_dao(&dao_id, &args),
+            "dao ID is already taken {}", dao_account_id
+        );
+        let storage_balance_used =
+            Balance::from(env::storage_usage() - initial_storage_usage) * STORAGE_PRICE_PER_BYTE;
+
+        let token_args = serde_json::json!({
+            "owner_id": account_id,
+            "total_supply": total_supply,
+            "metadata": token_metadata,
+        });
+        Promise::new(token_account_id)
+            .create_account()
+            .transfer(token_balance - storage_balance_used)
+            .deploy_contract(token_code)
+            .function_call(b"new".to_vec(), serde_json::to_vec(&token_args).unwrap(), 0, GAS)
+            .then(ext_self_launch::on_create_token(
+                dao_id,
+                account_id,
+                dao_balance.into(),
+                token_balance.into(),
+                &env::current_account_id(),
+                0,
+                GAS_FOR_ON_CREATE_TOKEN,
+            ))
+    }
+
+    // Deploys the DAO once its token is created, otherwise undoes the registration of both
+    // and credits the whole required balance back to the creator's storage deposit.
+    // If the DAO was delisted in the meantime, only its balance is credited back.
+    #[private]
+    pub fn on_create_token(
+        &mut self,
+        dao_id: DaoId,
+        account_id: AccountId,
+        dao_balance: U128,
+        token_balance: U128,
+    ) -> PromiseOrValue<bool> {
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => match self.daos.get(&dao_id) {
+                Some(args) => {
+                    let (_, code) = self.internal_get_code(args.code_hash);
+                    PromiseOrValue::Promise(self.internal_deploy_dao(
+                        dao_id,
+                        &args,
+                        code,
+                        dao_balance.0,
+                        account_id,
+                    ))
+                }
+                None => {
+                    env::log(format!("DAO {} was delisted before it was deployed", dao_id).as_bytes());
+                    self.internal_refund_deposit(account_id, dao_balance.0);
+                    PromiseOrValue::Value(false)
+                }
+            },
+            _ => {
+                self.internal_remove_dao(&dao_id);
+                self.allowed_tokens.remove(&token_account_id(&dao_id));
+                self.internal_refund_deposit(account_id, dao_balance.0 + token_balance.0);
+                PromiseOrValue::Value(false)
+            }
+        }
+    }
+}
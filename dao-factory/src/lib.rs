@@ -1,20 +1,34 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedMap};
+use near_sdk::collections::{LazyOption, LookupMap, TreeMap, UnorderedMap, UnorderedSet};
 use near_sdk::env::STORAGE_PRICE_PER_BYTE;
-use near_sdk::json_types::{ValidAccountId, U128};
-use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::json_types::{Base58CryptoHash, ValidAccountId, U128};
 use near_sdk::serde_json;
+use std::ops::Bound;
 use near_sdk::{
-    env, near_bindgen, AccountId, Balance, BorshStorageKey, Gas, PanicOnDefault, Promise,
+    assert_one_yocto, env, ext_contract, near_bindgen, AccountId, Balance, BorshStorageKey, CryptoHash, Gas,
+    PanicOnDefault, Promise, PromiseResult,
 };
 
-const DAO_WASM_CODE: &[u8] = include_bytes!("../../out/connecus-dao.wasm");
+pub use crate::migrations::*;
+pub use connesus_common::{DaoArgs, DaoMetadata, OldDaoMetadata};
+
+mod migrations;
+mod launch;
+mod storage;
+mod tokens;
 
 const EXTRA_BYTES: usize = 10000;
 const GAS: Gas = 50_000_000_000_000;
-type daoId = String;
+// Gas for deploying new code to a DAO and migrating its state.
+const GAS_FOR_UPGRADE: Gas = 150_000_000_000_000;
+const GAS_FOR_ON_UPGRADE: Gas = 10_000_000_000_000;
+const GAS_FOR_ON_CREATE: Gas = 10_000_000_000_000;
+// Gas for updating the owner on the DAO side.
+const GAS_FOR_SET_OWNER: Gas = 10_000_000_000_000;
+const GAS_FOR_ON_SET_OWNER: Gas = 10_000_000_000_000;
+type DaoId = String;
 
-pub fn is_valid_dao_id(dao_id: &daoId) -> bool {
+pub fn is_valid_dao_id(dao_id: &DaoId) -> bool {
     for c in dao_id.as_bytes() {
         match c {
             b'0'..=b'9' | b'a'..=b'z' => (),
@@ -26,8 +40,20 @@ pub fn is_valid_dao_id(dao_id: &daoId) -> bool {
 
 #[derive(BorshSerialize, BorshStorageKey)]
 enum StorageKey {
+    // Prefix of DAOs registered by version 1, see `migrations`. Kept so later variants keep their prefixes.
+    #[allow(dead_code)]
     Daos,
     StorageDeposits,
+    DaoCodes,
+    DaosByOwner,
+    DaosByOwnerIndex { owner_id: AccountId },
+    DaosByToken,
+    DaosByTokenIndex { token_contract_id: AccountId },
+    DaosByName,
+    AllowedTokens,
+    TokenCode,
+    RegisteredDaos,
+    DaoCodeSizes,
 }
 
 pub type OldAccountId = String;
@@ -38,45 +64,52 @@ pub type OldAccountId = String;
 pub struct DaoFactory {
     pub daos: UnorderedMap<AccountId, DaoArgs>,
     pub storage_deposits: LookupMap<AccountId, Balance>,
-    pub token_contract_id: ValidAccountId,
     pub storage_balance_cost: Balance,
+    // DAO code versions by sha256 hash of the code.
+    pub dao_codes: UnorderedMap<CryptoHash, Vec<u8>>,
+    // Sizes of stored DAO code versions, so deposits are computed without loading the code.
+    pub dao_code_sizes: LookupMap<CryptoHash, u64>,
+    // Code deployed to newly created DAOs when no code hash is given.
+    pub default_code_hash: Option<CryptoHash>,
+    // Account allowed to upload DAO code.
+    pub owner_id: AccountId,
+    // DAO ids grouped by owner.
+    pub daos_by_owner: LookupMap<AccountId, UnorderedSet<DaoId>>,
+    // DAO ids grouped by token contract.
+    pub daos_by_token: LookupMap<AccountId, UnorderedSet<DaoId>>,
+    // DAO ids ordered by lowercase name, for prefix search.
+    pub daos_by_name: TreeMap<(String, DaoId), ()>,
+    // Token contracts DAOs can be created for.
+    pub allowed_tokens: UnorderedSet<AccountId>,
+    // Whether anyone can add a token contract to the allow-list after its metadata is verified.
+    pub open_token_listing: bool,
+    // Community token code deployed by `create_dao_with_token`.
+    pub token_code: LazyOption<Vec<u8>>,
+    // Size of the stored community token code.
+    pub token_code_size: u64,
+    // DAOs registered by version 1 that are not moved to `daos` yet, see `migrate_daos`.
+    pub pending_daos: Option<UnorderedMap<AccountId, OldDaoArgs>>,
+    // Version of the state layout, see `migrations`.
+    pub state_version: u32,
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Clone, Deserialize, Serialize)]
-#[serde(crate = "near_sdk::serde")]
-pub struct DaoMetadata {
-    // Name of the DAO.
-    pub name: String,
-
-    // Purpose of this DAO.
-    pub purpose: String,
-    // Generic metadata. Can be used by specific UI to store additional data.
-    // This is not used by anything in the contract.
-    pub thumbnail: String,
-
-    pub symbol: String,
-
-    pub facebook: Option<String>,
-
-    pub twitter: Option<String>,
-
-    pub discord: Option<String>,
-
-    pub instagram: Option<String>,
+#[ext_contract(ext_self)]
+pub trait ExtDaoFactory {
+    fn on_upgrade_dao(&mut self, dao_id: AccountId, code_hash: Base58CryptoHash) -> bool;
+    fn on_create(&mut self, dao_id: AccountId, account_id: AccountId, attached_balance: U128) -> bool;
+    fn on_transfer_dao_ownership(&mut self, dao_id: AccountId, new_owner_id: AccountId) -> bool;
 }
 
-#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
-#[serde(crate = "near_sdk::serde")]
-pub struct DaoArgs {
-    token_contract_id: OldAccountId,
-    metadata: DaoMetadata,
-    owner_id: AccountId
+#[ext_contract(ext_dao)]
+pub trait ExtDao {
+    fn set_owner_from_factory(&mut self, new_owner_id: AccountId);
 }
 
 #[near_bindgen]
 impl DaoFactory {
+    // `token_contract_id` is the first token contract DAOs can be created for.
     #[init]
-    pub fn new(token_contract_id: ValidAccountId) -> Self {
+    pub fn new(token_contract_id: ValidAccountId, owner_id: ValidAccountId) -> Self {
         let mut storage_deposits = LookupMap::new(StorageKey::StorageDeposits);
 
         let initial_storage_usage = env::storage_usage();
@@ -86,64 +119,216 @@ impl DaoFactory {
             Balance::from(env::storage_usage() - initial_storage_usage) * STORAGE_PRICE_PER_BYTE;
         storage_deposits.remove(&tmp_account_id);
 
-        Self {
-            daos: UnorderedMap::new(StorageKey::Daos),
+        let mut this = Self {
+            daos: UnorderedMap::new(StorageKey::RegisteredDaos),
             storage_deposits,
             storage_balance_cost,
-            token_contract_id: token_contract_id
-        }
+            dao_codes: UnorderedMap::new(StorageKey::DaoCodes),
+            dao_code_sizes: LookupMap::new(StorageKey::DaoCodeSizes),
+            default_code_hash: None,
+            owner_id: owner_id.into(),
+            daos_by_owner: LookupMap::new(StorageKey::DaosByOwner),
+            daos_by_token: LookupMap::new(StorageKey::DaosByToken),
+            daos_by_name: TreeMap::new(StorageKey::DaosByName),
+            allowed_tokens: UnorderedSet::new(StorageKey::AllowedTokens),
+            open_token_listing: false,
+            token_code: LazyOption::new(StorageKey::TokenCode, None),
+            token_code_size: 0,
+            pending_daos: None,
+            state_version: STATE_VERSION,
+        };
+        this.allowed_tokens.insert(token_contract_id.as_ref());
+        this
     }
 
+    // Upgrades state written by any previous version of the contract to the current layout.
+    // The token of version 1 becomes the first entry of the allow-list.
     #[init(ignore_state)]
-    pub fn migrate(token_contract_id: ValidAccountId) -> Self {
+    pub fn migrate() -> Self {
         assert_eq!(
             env::predecessor_account_id(),
             env::current_account_id(),
             "ERR_NOT_ALLOWED"
         );
-        let this: Contract = env::state_read().expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
-        let mut storage_deposits = LookupMap::new(StorageKey::StorageDeposits);
-        let initial_storage_usage = env::storage_usage();
-        let tmp_account_id = "a".repeat(64);
-        storage_deposits.insert(&tmp_account_id, &0);
-        let storage_balance_cost =
-            Balance::from(env::storage_usage() - initial_storage_usage) * STORAGE_PRICE_PER_BYTE;
-        storage_deposits.remove(&tmp_account_id);
+        let state = env::storage_read(b"STATE").expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
+        migrate_state(&state)
+    }
 
-        Self {
-            daos: UnorderedMap::new(StorageKey::Daos),
-            storage_deposits,
-            storage_balance_cost,
-            token_contract_id: token_contract_id
+    // Stores DAO code under its sha256 hash if not stored yet.
+    fn internal_store_code(&mut self, code: Vec<u8>) -> CryptoHash {
+        let mut code_hash = CryptoHash::default();
+        code_hash.copy_from_slice(&env::sha256(&code));
+        if self.dao_code_sizes.get(&code_hash).is_none() {
+            self.dao_code_sizes.insert(&code_hash, &(code.len() as u64));
+            self.dao_codes.insert(&code_hash, &code);
         }
+        code_hash
     }
 
-    fn get_min_attached_balance(&self, args: &DaoArgs) -> u128 {
-        ((DAO_WASM_CODE.len() + EXTRA_BYTES + args.try_to_vec().unwrap().len() * 2) as Balance
-            * STORAGE_PRICE_PER_BYTE + 5)
-            .into()
+    fn internal_get_code(&self, code_hash: Option<Base58CryptoHash>) -> (CryptoHash, Vec<u8>) {
+        let code_hash = self.internal_code_hash(code_hash);
+        let code = self.dao_codes.get(&code_hash).expect("Code not found");
+        (code_hash, code)
     }
 
-    pub fn get_required_deposit(&self, args: DaoArgs, account_id: ValidAccountId) -> U128 {
-        let args_deposit = self.get_min_attached_balance(&args);
-        if let Some(previous_balance) = self.storage_deposits.get(account_id.as_ref()) {
-            args_deposit.saturating_sub(previous_balance).into()
-        } else {
-            (self.storage_balance_cost + args_deposit).into()
+    // Returns hash and size of given DAO code version without loading the code.
+    pub(crate) fn internal_get_code_size(&self, code_hash: Option<Base58CryptoHash>) -> (CryptoHash, usize) {
+        let code_hash = self.internal_code_hash(code_hash);
+        let code_size = self.dao_code_sizes.get(&code_hash).expect("Code not found");
+        (code_hash, code_size as usize)
+    }
+
+    fn internal_code_hash(&self, code_hash: Option<Base58CryptoHash>) -> CryptoHash {
+        code_hash
+            .map(|code_hash| code_hash.into())
+            .or(self.default_code_hash)
+            .expect("No default code")
+    }
+
+    // Registers the DAO and adds it to the owner, token and name indexes.
+    // Returns false if the DAO id is already taken.
+    pub(crate) fn internal_add_dao(&mut self, dao_id: &DaoId, args: &DaoArgs) -> bool {
+        if self.daos.get(dao_id).is_some() {
+            return false;
         }
+        self.daos.insert(dao_id, args);
+        self.internal_index_dao(dao_id, args);
+        true
     }
 
-    #[payable]
-    pub fn storage_deposit(&mut self) {
-        let account_id = env::predecessor_account_id();
-        let deposit = env::attached_deposit();
-        if let Some(previous_balance) = self.storage_deposits.get(&account_id) {
+    // Adds the DAO to the owner, token and name indexes.
+    pub(crate) fn internal_index_dao(&mut self, dao_id: &DaoId, args: &DaoArgs) {
+        let mut owner_daos = self.daos_by_owner.get(&args.owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::DaosByOwnerIndex {
+                owner_id: args.owner_id.clone(),
+            })
+        });
+        owner_daos.insert(dao_id);
+        self.daos_by_owner.insert(&args.owner_id, &owner_daos);
+        let mut token_daos = self
+            .daos_by_token
+            .get(&args.token_contract_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(StorageKey::DaosByTokenIndex {
+                    token_contract_id: args.token_contract_id.clone(),
+                })
+            });
+        token_daos.insert(dao_id);
+        self.daos_by_token.insert(&args.token_contract_id, &token_daos);
+        self.daos_by_name
+            .insert(&(args.metadata.name.to_lowercase(), dao_id.clone()), &());
+    }
+
+    // Removes the DAO from the registry and all indexes.
+    pub(crate) fn internal_remove_dao(&mut self, dao_id: &DaoId) -> Option<DaoArgs> {
+        let args = self.daos.remove(dao_id)?;
+        Self::remove_from_index(&mut self.daos_by_owner, &args.owner_id, dao_id);
+        Self::remove_from_index(&mut self.daos_by_token, &args.token_contract_id, dao_id);
+        self.daos_by_name
+            .remove(&(args.metadata.name.to_lowercase(), dao_id.clone()));
+        Some(args)
+    }
+
+    fn remove_from_index(index: &mut LookupMap<AccountId, UnorderedSet<DaoId>>, key: &AccountId, dao_id: &DaoId) {
+        if let Some(mut daos) = index.get(key) {
+            daos.remove(dao_id);
+            if daos.is_empty() {
+                index.remove(key);
+            } else {
+                index.insert(key, &daos);
+            }
+        }
+    }
+
+    fn get_indexed_daos(&self, daos: Option<UnorderedSet<DaoId>>, from_index: u64, limit: u64) -> Vec<(DaoId, DaoArgs)> {
+        daos.map(|daos| {
+            let dao_ids = daos.as_vector();
+            (from_index..std::cmp::min(from_index + limit, dao_ids.len()))
+                .filter_map(|index| dao_ids.get(index))
+                .filter_map(|dao_id| self.daos.get(&dao_id).map(|args| (dao_id, args)))
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+
+    // Returns id and account of the DAO with given metadata, checking both are valid.
+    pub(crate) fn internal_dao_account_id(&self, metadata: &DaoMetadata) -> (DaoId, AccountId) {
+        let dao_id = metadata.symbol.to_ascii_lowercase();
+        assert!(is_valid_dao_id(&dao_id), "Invalid Symbol");
+        let dao_account_id = format!("{}.{}", dao_id, env::current_account_id());
+        assert!(
+            env::is_valid_account_id(dao_account_id.as_bytes()),
+            "dao Account ID is invalid"
+        );
+        (dao_id, dao_account_id)
+    }
+
+    pub(crate) fn internal_withdraw_deposit(&mut self, account_id: &AccountId, amount: Balance) {
+        let user_balance = self.storage_deposits.get(account_id).unwrap_or(0);
+        assert!(
+            user_balance >= amount,
+            "Not enough required balance"
+        );
+        self.storage_deposits
+            .insert(account_id, &(user_balance - amount));
+    }
+
+    // DAOs created by the first factory version have no `update_from_factory` or
+    // `set_owner_from_factory`, so the factory can't upgrade them or transfer their ownership.
+    fn assert_managed_dao(args: &DaoArgs) {
+        assert!(
+            args.code_hash.is_some(),
+            "dao was created by a previous factory version and can't be managed by the factory"
+        );
+    }
+
+    // Credits amount back to the account's storage deposit, or sends it if the account unregistered since.
+    pub(crate) fn internal_refund_deposit(&mut self, account_id: AccountId, amount: Balance) {
+        if let Some(balance) = self.storage_deposits.get(&account_id) {
             self.storage_deposits
-                .insert(&account_id, &(previous_balance + deposit));
+                .insert(&account_id, &(balance + amount));
         } else {
-            assert!(deposit >= self.storage_balance_cost, "Deposit is too low");
-            self.storage_deposits
-                .insert(&account_id, &(deposit - self.storage_balance_cost));
+            Promise::new(account_id).transfer(amount);
+        }
+    }
+
+    // Creates the DAO account with given balance, deploys code and initializes it.
+    // The balance goes back to the creator if any of it fails, see `on_create`.
+    pub(crate) fn internal_deploy_dao(
+        &self,
+        dao_id: DaoId,
+        args: &DaoArgs,
+        code: Vec<u8>,
+        amount: Balance,
+        account_id: AccountId,
+    ) -> Promise {
+        Promise::new(format!("{}.{}", dao_id, env::current_account_id()))
+            .create_account()
+            .transfer(amount)
+            .deploy_contract(code)
+            .function_call(b"new".to_vec(), serde_json::to_vec(args).unwrap(), 0, GAS)
+            .then(ext_self::on_create(
+                dao_id,
+                account_id,
+                amount.into(),
+                &env::current_account_id(),
+                0,
+                GAS_FOR_ON_CREATE,
+            ))
+    }
+
+    fn get_min_attached_balance(&self, args: &DaoArgs, code_len: usize) -> u128 {
+        (code_len + EXTRA_BYTES + args.try_to_vec().unwrap().len() * 2) as Balance
+            * STORAGE_PRICE_PER_BYTE + 5
+    }
+
+    pub fn get_required_deposit(&self, args: DaoArgs, account_id: ValidAccountId, code_hash: Option<Base58CryptoHash>) -> U128 {
+        let (_, code_size) = self.internal_get_code_size(code_hash);
+        let args_deposit = self.get_min_attached_balance(&args, code_size);
+        if let Some(previous_balance) = self.storage_deposits.get(account_id.as_ref()) {
+            args_deposit.saturating_sub(previous_balance).into()
+        } else {
+            (self.storage_balance_cost + args_deposit).into()
         }
     }
 
@@ -154,7 +339,7 @@ impl DaoFactory {
     pub fn get_daos(&self, from_index: u64, limit: u64) -> Vec<(u64, DaoArgs)> {
         let daos = self.daos.values_as_vector();
         (from_index..std::cmp::min(from_index + limit, daos.len()))
-            .filter_map(|index| (index, daos.get(index)))
+            .filter_map(|index| daos.get(index).map(|dao| (index, dao)))
             .collect()
     }
 
@@ -162,65 +347,887 @@ impl DaoFactory {
         self.daos.get(&dao_id)
     }
 
+    pub fn get_daos_by_owner(&self, owner_id: ValidAccountId, from_index: u64, limit: u64) -> Vec<(DaoId, DaoArgs)> {
+        self.get_indexed_daos(self.daos_by_owner.get(owner_id.as_ref()), from_index, limit)
+    }
+
+    pub fn get_daos_by_token(&self, token_contract_id: ValidAccountId, from_index: u64, limit: u64) -> Vec<(DaoId, DaoArgs)> {
+        self.get_indexed_daos(self.daos_by_token.get(token_contract_id.as_ref()), from_index, limit)
+    }
+
+    // Returns DAOs whose name starts with given prefix, ignoring case, ordered by name.
+    pub fn search_daos_by_name(&self, prefix: String, from_index: u64, limit: u64) -> Vec<(DaoId, DaoArgs)> {
+        let prefix = prefix.to_lowercase();
+        self.daos_by_name
+            .range((Bound::Included((prefix.clone(), String::new())), Bound::Unbounded))
+            .take_while(|((name, _), _)| name.starts_with(&prefix))
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter_map(|((_, dao_id), _)| self.daos.get(&dao_id).map(|args| (dao_id, args)))
+            .collect()
+    }
+
+    pub fn get_code_hashes(&self) -> Vec<Base58CryptoHash> {
+        self.dao_codes
+            .keys()
+            .map(|code_hash| code_hash.into())
+            .collect()
+    }
+
+    pub fn get_default_code_hash(&self) -> Option<Base58CryptoHash> {
+        self.default_code_hash.map(|code_hash| code_hash.into())
+    }
+
+    // Stores DAO code passed as raw input and returns its hash. Only the factory owner can upload code.
+    // Attached deposit must cover the storage used, the rest is refunded.
     #[payable]
-    pub fn create_dao(&mut self, args: DaoArgs) -> Promise {
-        if env::attached_deposit() > 0 {
-            self.storage_deposit();
+    pub fn store_code(&mut self) -> Base58CryptoHash {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only owner can store code"
+        );
+        let code = env::input().expect("No code attached");
+        let initial_storage_usage = env::storage_usage();
+        let code_hash = self.internal_store_code(code);
+        let storage_cost =
+            Balance::from(env::storage_usage() - initial_storage_usage) * STORAGE_PRICE_PER_BYTE;
+        let deposit = env::attached_deposit();
+        assert!(deposit >= storage_cost, "Deposit is too low, required {}", storage_cost);
+        if deposit > storage_cost {
+            Promise::new(env::predecessor_account_id()).transfer(deposit - storage_cost);
         }
-        let dao_id = args.metadata.symbol.to_ascii_lowercase();
-        assert!(is_valid_dao_id(&dao_id), "Invalid Symbol");
-        let dao_account_id = format!("{}.{}", dao_id, env::current_account_id());
-        assert!(
-            env::is_valid_account_id(dao_account_id.as_bytes()),
-            "dao Account ID is invalid"
+        if self.default_code_hash.is_none() {
+            self.default_code_hash = Some(code_hash);
+        }
+        code_hash.into()
+    }
+
+    // Sets code deployed to new DAOs when no code hash is given. Only the factory owner can change it.
+    pub fn set_default_code_hash(&mut self, code_hash: Base58CryptoHash) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only owner can set default code"
+        );
+        let code_hash: CryptoHash = code_hash.into();
+        assert!(self.dao_codes.get(&code_hash).is_some(), "Code not found");
+        self.default_code_hash = Some(code_hash);
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    // Hands the factory over to another account. Only the factory owner can change it.
+    // Requires 1 yoctoNEAR.
+    #[payable]
+    pub fn set_owner(&mut self, owner_id: ValidAccountId) {
+        assert_one_yocto();
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only owner can set owner"
         );
+        self.owner_id = owner_id.into();
+    }
 
+    // Deploys stored code version to the DAO. Only the DAO owner can upgrade it.
+    // Requires 1 yoctoNEAR.
+    #[payable]
+    pub fn upgrade_dao(&mut self, dao_id: AccountId, code_hash: Base58CryptoHash) -> Promise {
+        assert_one_yocto();
+        let args = self.daos.get(&dao_id).expect("dao not found");
         assert_eq!(
-            self.token_contract_id,
-            args.token_contract_id,
-            "dao Token ID is invalid"
+            env::predecessor_account_id(),
+            args.owner_id,
+            "Only dao owner can upgrade"
         );
+        Self::assert_managed_dao(&args);
+        let code = self.dao_codes.get(&code_hash.into()).expect("Code not found");
+        let dao_account_id = format!("{}.{}", dao_id, env::current_account_id());
+        Promise::new(dao_account_id)
+            .function_call(b"update_from_factory".to_vec(), code, 0, GAS_FOR_UPGRADE)
+            .then(ext_self::on_upgrade_dao(
+                dao_id,
+                code_hash,
+                &env::current_account_id(),
+                0,
+                GAS_FOR_ON_UPGRADE,
+            ))
+    }
 
-        let account_id = env::predecessor_account_id();
+    #[private]
+    pub fn on_upgrade_dao(&mut self, dao_id: AccountId, code_hash: Base58CryptoHash) -> bool {
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                if let Some(mut args) = self.daos.get(&dao_id) {
+                    args.code_hash = Some(code_hash);
+                    self.daos.insert(&dao_id, &args);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Transfers ownership of the DAO to another account, both on the DAO and in the registry.
+    // Only the DAO owner can transfer it. Requires 1 yoctoNEAR.
+    #[payable]
+    pub fn transfer_dao_ownership(&mut self, dao_id: AccountId, new_owner_id: ValidAccountId) -> Promise {
+        assert_one_yocto();
+        let args = self.daos.get(&dao_id).expect("dao not found");
+        assert_eq!(
+            env::predecessor_account_id(),
+            args.owner_id,
+            "Only dao owner can transfer ownership"
+        );
+        Self::assert_managed_dao(&args);
+        let dao_account_id = format!("{}.{}", dao_id, env::current_account_id());
+        ext_dao::set_owner_from_factory(
+            new_owner_id.to_string(),
+            &dao_account_id,
+            0,
+            GAS_FOR_SET_OWNER,
+        )
+        .then(ext_self::on_transfer_dao_ownership(
+            dao_id,
+            new_owner_id.into(),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_ON_SET_OWNER,
+        ))
+    }
 
-        let required_balance = self.get_min_attached_balance(&args);
-        let user_balance = self.storage_deposits.get(&account_id).unwrap_or(0);
+    #[private]
+    pub fn on_transfer_dao_ownership(&mut self, dao_id: AccountId, new_owner_id: AccountId) -> bool {
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                if let Some(args) = self.daos.get(&dao_id) {
+                    Self::remove_from_index(&mut self.daos_by_owner, &args.owner_id, &dao_id);
+                    let args = DaoArgs {
+                        owner_id: new_owner_id,
+                        ..args
+                    };
+                    self.daos.insert(&dao_id, &args);
+                    self.internal_index_dao(&dao_id, &args);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Syncs metadata after the DAO owner updated it, see `update_metadata` on the DAO.
+    // Only the DAO account itself can call it. Attached deposit pays for the extra storage,
+    // the rest is refunded to the DAO owner.
+    #[payable]
+    pub fn update_dao_metadata(&mut self, metadata: DaoMetadata) {
+        let dao_account_id = env::predecessor_account_id();
+        let (dao_id, factory_id) = dao_account_id.split_once('.').expect("dao not found");
+        assert_eq!(factory_id, env::current_account_id(), "dao not found");
+        let dao_id = dao_id.to_string();
+        let mut args = self.daos.get(&dao_id).expect("dao not found");
+        metadata.assert_valid();
+        assert_eq!(metadata.symbol, args.metadata.symbol, "dao Symbol can't be changed");
+
+        let initial_storage_usage = env::storage_usage();
+        self.daos_by_name
+            .remove(&(args.metadata.name.to_lowercase(), dao_id.clone()));
+        self.daos_by_name
+            .insert(&(metadata.name.to_lowercase(), dao_id.clone()), &());
+        args.metadata = metadata;
+        self.daos.insert(&dao_id, &args);
+        let storage_cost = Balance::from(env::storage_usage().saturating_sub(initial_storage_usage))
+            * STORAGE_PRICE_PER_BYTE;
+        let deposit = env::attached_deposit();
+        assert!(deposit >= storage_cost, "Deposit is too low, required {}", storage_cost);
+        if deposit > storage_cost {
+            Promise::new(args.owner_id).transfer(deposit - storage_cost);
+        }
+    }
+
+    // Removes the DAO from the registry. The DAO account itself is left untouched.
+    // Can be called by the DAO owner or the factory owner. Released storage is refunded to the DAO
+    // owner, who paid for it.
+    pub fn delist_dao(&mut self, dao_id: AccountId) {
+        let args = self.daos.get(&dao_id).expect("dao not found");
+        let predecessor_id = env::predecessor_account_id();
         assert!(
-            user_balance >= required_balance,
-            "Not enough required balance"
+            predecessor_id == args.owner_id || predecessor_id == self.owner_id,
+            "Only dao owner or factory owner can delist"
         );
-        self.storage_deposits
-            .insert(&account_id, &(user_balance - required_balance));
+        let initial_storage_usage = env::storage_usage();
+        self.internal_remove_dao(&dao_id);
+        let storage_balance_released =
+            Balance::from(initial_storage_usage - env::storage_usage()) * STORAGE_PRICE_PER_BYTE;
+        if storage_balance_released > 0 {
+            Promise::new(args.owner_id).transfer(storage_balance_released);
+        }
+    }
+
+    #[payable]
+    pub fn create_dao(&mut self, args: DaoArgs, code_hash: Option<Base58CryptoHash>) -> Promise {
+        self.assert_daos_migrated();
+        if env::attached_deposit() > 0 {
+            self.internal_storage_deposit(&env::predecessor_account_id(), env::attached_deposit());
+        }
+        args.metadata.assert_valid();
+        let (dao_id, dao_account_id) = self.internal_dao_account_id(&args.metadata);
+
+        assert!(
+            self.allowed_tokens.contains(&args.token_contract_id),
+            "dao Token ID is not allowed"
+        );
+
+        let account_id = env::predecessor_account_id();
+
+        let (code_hash, code) = self.internal_get_code(code_hash);
+        let required_balance = self.get_min_attached_balance(&args, code.len());
+        self.internal_withdraw_deposit(&account_id, required_balance);
 
         let initial_storage_usage = env::storage_usage();
 
+        let args = DaoArgs {
+            code_hash: Some(code_hash.into()),
+            ..args
+        };
         assert!(
-            self.daos.insert(&dao_id, &args).is_none(),
+            self.internal_add_dao(&dao_id, &args),
             "dao ID is already taken {}", dao_account_id
         );
 
         let storage_balance_used =
             Balance::from(env::storage_usage() - initial_storage_usage) * STORAGE_PRICE_PER_BYTE;
 
-        Promise::new(dao_account_id)
-            .create_account()
-            .transfer(required_balance - storage_balance_used)
-            .deploy_contract(DAO_WASM_CODE.to_vec())
-            .function_call(b"new".to_vec(), serde_json::to_vec(&args).unwrap(), 0, GAS)
+        self.internal_deploy_dao(
+            dao_id,
+            &args,
+            code,
+            required_balance - storage_balance_used,
+            account_id,
+        )
     }
 
-    
+    // Undoes the registration if the DAO account could not be created.
+    // The balance sent to the failed account comes back to the factory, so it is credited back
+    // to the creator's storage deposit together with the storage released by the registration.
+    #[private]
+    pub fn on_create(&mut self, dao_id: AccountId, account_id: AccountId, attached_balance: U128) -> bool {
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => true,
+            _ => {
+                let initial_storage_usage = env::storage_usage();
+                self.internal_remove_dao(&dao_id);
+                let storage_balance_released = Balance::from(initial_storage_usage - env::storage_usage())
+                    * STORAGE_PRICE_PER_BYTE;
+                self.internal_refund_deposit(account_id, attached_balance.0 + storage_balance_released);
+                false
+            }
+        }
+    }
 }
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use super::*;
+    use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+    use near_contract_standards::storage_management::StorageManagement;
+    use std::convert::TryInto;
+
+    use near_sdk::test_utils::{get_created_receipts, VMContextBuilder, accounts};
+    use near_sdk::{testing_env, env};
+    use near_sdk::{MockedBlockchain, RuntimeFeesConfig, VMConfig};
+
+    const STORE_CODE_DEPOSIT: Balance = 10_000_000_000_000_000_000_000_000;
+
+    fn get_context(predecessor_account_id: ValidAccountId) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(accounts(0))
+            .signer_account_id(predecessor_account_id.clone())
+            .predecessor_account_id(predecessor_account_id);
+        builder
+    }
+
+    fn dao_args(symbol: &str) -> DaoArgs {
+        DaoArgs {
+            token_contract_id: accounts(2).into(),
+            metadata: DaoMetadata {
+                name: "Test".to_string(),
+                purpose: "Test".to_string(),
+                thumbnail: "".to_string(),
+                symbol: symbol.to_string(),
+                facebook: None,
+                twitter: None,
+                discord: None,
+                instagram: None,
+                youtube: None,
+            },
+            owner_id: accounts(3).into(),
+            code_hash: None,
+        }
+    }
+
+    // Uploads the code as the factory owner and returns its hash.
+    fn store_test_code(
+        context: &mut VMContextBuilder,
+        contract: &mut DaoFactory,
+        code: Vec<u8>,
+    ) -> Base58CryptoHash {
+        let mut vm_context = context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORE_CODE_DEPOSIT)
+            .build();
+        vm_context.input = code;
+        testing_env!(vm_context);
+        contract.store_code()
+    }
+
+    #[test]
+    fn test_store_code() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        assert_eq!(contract.get_default_code_hash(), None);
+
+        let code = vec![1u8; 100];
+        let code_hash = store_test_code(&mut context, &mut contract, code.clone());
+
+        assert_eq!(CryptoHash::from(code_hash).to_vec(), env::sha256(&code));
+        assert_eq!(contract.get_default_code_hash(), Some(code_hash));
+        assert_eq!(contract.get_code_hashes(), vec![code_hash]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can store code")]
+    fn test_store_code_not_owner() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+
+        let mut vm_context = context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(STORE_CODE_DEPOSIT)
+            .build();
+        vm_context.input = vec![1u8; 100];
+        testing_env!(vm_context);
+        contract.store_code();
+    }
+
+    #[test]
+    fn test_on_create_failed_refunds_deposit() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        store_test_code(&mut context, &mut contract, vec![1u8; 100]);
+
+        let deposit = STORE_CODE_DEPOSIT;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(deposit)
+            .build());
+        contract.storage_deposit(None, None);
+        testing_env!(context.attached_deposit(0).build());
+        let initial_storage_usage = env::storage_usage();
+        contract.create_dao(dao_args("test"), None);
+        let storage_balance_used =
+            Balance::from(env::storage_usage() - initial_storage_usage) * STORAGE_PRICE_PER_BYTE;
+        assert!(contract.get_dao("test".to_string()).is_some());
+        let balance = contract.storage_deposits.get(accounts(3).as_ref()).unwrap();
+        assert!(balance < deposit - contract.storage_balance_cost);
+
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            VMConfig::default(),
+            RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        let required_balance = deposit - contract.storage_balance_cost - balance;
+        let attached_balance = required_balance - storage_balance_used;
+        assert!(!contract.on_create("test".to_string(), accounts(3).into(), attached_balance.into()));
+        assert!(contract.get_dao("test".to_string()).is_none());
+        assert_eq!(
+            contract.storage_deposits.get(accounts(3).as_ref()).unwrap(),
+            deposit - contract.storage_balance_cost
+        );
+    }
+
+    #[test]
+    fn test_storage_withdraw() {
+        let mut context = get_context(accounts(3));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        let deposit = 10 * contract.storage_balance_cost;
+
+        testing_env!(context.attached_deposit(deposit).build());
+        contract.storage_deposit(None, None);
+        let balance = contract.storage_balance_of(accounts(3)).unwrap();
+        assert_eq!(balance.total.0, deposit);
+        assert_eq!(balance.available.0, deposit - contract.storage_balance_cost);
+
+        testing_env!(context.attached_deposit(1).build());
+        let balance = contract.storage_withdraw(Some(contract.storage_balance_cost.into()));
+        assert_eq!(balance.available.0, deposit - 2 * contract.storage_balance_cost);
+        assert!(contract.storage_unregister(None));
+        assert!(contract.storage_balance_of(accounts(3)).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Deposit is too low")]
+    fn test_storage_deposit_registration_only_too_low() {
+        let mut context = get_context(accounts(3));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+
+        testing_env!(context.attached_deposit(contract.storage_balance_cost - 1).build());
+        contract.storage_deposit(None, Some(true));
+    }
+
+    #[test]
+    #[should_panic(expected = "Force unregister is not supported")]
+    fn test_storage_unregister_force() {
+        let mut context = get_context(accounts(3));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+
+        testing_env!(context.attached_deposit(contract.storage_balance_cost).build());
+        contract.storage_deposit(None, Some(true));
+        testing_env!(context.attached_deposit(1).build());
+        contract.storage_unregister(Some(true));
+    }
 
-    use near_sdk::test_utils::{VMContextBuilder, accounts};
-    use near_sdk::{testing_env, env, Balance};
-    use near_sdk::MockedBlockchain;
+    fn create_test_dao(context: &mut VMContextBuilder, contract: &mut DaoFactory) {
+        store_test_code(context, contract, vec![1u8; 100]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(STORE_CODE_DEPOSIT)
+            .build());
+        contract.create_dao(dao_args("test"), None);
+    }
+
+    #[test]
+    fn test_daos_indexes() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        create_test_dao(&mut context, &mut contract);
+        let mut args = dao_args("other");
+        args.metadata.name = "Other".to_string();
+        contract.create_dao(args, None);
+
+        let owner_daos = contract.get_daos_by_owner(accounts(3), 0, 10);
+        assert_eq!(owner_daos.len(), 2);
+        assert!(contract.get_daos_by_owner(accounts(4), 0, 10).is_empty());
+        assert_eq!(contract.get_daos_by_token(accounts(2), 1, 10).len(), 1);
 
-    const MINT_STORAGE_COST: u128 = 58700000000000000000000;
-    const TOTAL_SUPPLY: Balance = 1_000_000_000_000_000;
-    
+        let found = contract.search_daos_by_name("te".to_string(), 0, 10);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "test".to_string());
+        assert_eq!(contract.search_daos_by_name("OTH".to_string(), 0, 10)[0].0, "other".to_string());
+        assert_eq!(contract.search_daos_by_name("".to_string(), 0, 10).len(), 2);
+
+        testing_env!(context.attached_deposit(0).build());
+        contract.delist_dao("test".to_string());
+        assert_eq!(contract.get_daos_by_owner(accounts(3), 0, 10).len(), 1);
+        assert!(contract.search_daos_by_name("te".to_string(), 0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_transfer_dao_ownership() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        create_test_dao(&mut context, &mut contract);
+
+        testing_env!(context.attached_deposit(1).build());
+        contract.transfer_dao_ownership("test".to_string(), accounts(4));
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            VMConfig::default(),
+            RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        assert!(contract.on_transfer_dao_ownership("test".to_string(), accounts(4).into()));
+        assert_eq!(
+            contract.get_dao("test".to_string()).unwrap().owner_id,
+            accounts(4).to_string()
+        );
+        assert!(contract.get_daos_by_owner(accounts(3), 0, 10).is_empty());
+        assert_eq!(contract.get_daos_by_owner(accounts(4), 0, 10).len(), 1);
+    }
+
+    // Stores another code version as the factory owner and returns its hash.
+    fn store_other_code(context: &mut VMContextBuilder, contract: &mut DaoFactory) -> Base58CryptoHash {
+        store_test_code(context, contract, vec![2u8; 100])
+    }
+
+    #[test]
+    fn test_upgrade_dao() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        create_test_dao(&mut context, &mut contract);
+        let old_code_hash = contract.get_dao("test".to_string()).unwrap().code_hash;
+        let code_hash = store_other_code(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.upgrade_dao("test".to_string(), code_hash);
+        assert_eq!(contract.get_dao("test".to_string()).unwrap().code_hash, old_code_hash);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            VMConfig::default(),
+            RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        assert!(contract.on_upgrade_dao("test".to_string(), code_hash));
+        assert_eq!(contract.get_dao("test".to_string()).unwrap().code_hash, Some(code_hash));
+    }
+
+    #[test]
+    fn test_upgrade_dao_failed_keeps_version() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        create_test_dao(&mut context, &mut contract);
+        let old_code_hash = contract.get_dao("test".to_string()).unwrap().code_hash;
+        let code_hash = store_other_code(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.upgrade_dao("test".to_string(), code_hash);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            VMConfig::default(),
+            RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert!(!contract.on_upgrade_dao("test".to_string(), code_hash));
+        assert_eq!(contract.get_dao("test".to_string()).unwrap().code_hash, old_code_hash);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only dao owner can upgrade")]
+    fn test_upgrade_dao_not_owner() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        create_test_dao(&mut context, &mut contract);
+        let code_hash = store_other_code(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        contract.upgrade_dao("test".to_string(), code_hash);
+    }
+
+    #[test]
+    #[should_panic(expected = "Code not found")]
+    fn test_upgrade_dao_unknown_code() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        create_test_dao(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.upgrade_dao("test".to_string(), Base58CryptoHash::from([7u8; 32]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only dao owner can transfer ownership")]
+    fn test_transfer_dao_ownership_not_owner() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        create_test_dao(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        contract.transfer_dao_ownership("test".to_string(), accounts(4));
+    }
+
+    #[test]
+    fn test_set_owner() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        testing_env!(context.attached_deposit(1).build());
+        contract.set_owner(accounts(4));
+        assert_eq!(contract.get_owner(), accounts(4).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can set owner")]
+    fn test_set_owner_not_owner() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        contract.set_owner(accounts(4));
+    }
+
+    #[test]
+    fn test_delist_dao() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        create_test_dao(&mut context, &mut contract);
+        let balance = contract.storage_deposits.get(accounts(3).as_ref()).unwrap();
+
+        testing_env!(context.attached_deposit(0).build());
+        contract.delist_dao("test".to_string());
+        assert!(contract.get_dao("test".to_string()).is_none());
+        assert_eq!(contract.get_number_of_daos(), 0);
+        assert_eq!(contract.storage_deposits.get(accounts(3).as_ref()).unwrap(), balance);
+    }
+
+    #[test]
+    fn test_delist_dao_by_factory_owner_refunds_dao_owner() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        create_test_dao(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(0).build());
+        contract.delist_dao("test".to_string());
+        assert!(contract.get_dao("test".to_string()).is_none());
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        let receipt = near_sdk::serde_json::to_string(&receipts[0]).unwrap();
+        assert!(receipt.contains(&format!("\"receiver_id\":\"{}\"", accounts(3))));
+        assert!(receipt.contains("Transfer"));
+    }
+
+    #[test]
+    #[should_panic(expected = "dao Token ID is not allowed")]
+    fn test_create_dao_token_not_allowed() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        store_test_code(&mut context, &mut contract, vec![1u8; 100]);
+
+        let mut args = dao_args("test");
+        args.token_contract_id = accounts(4).into();
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.create_dao(args, None);
+    }
+
+    #[test]
+    fn test_register_token() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        contract.set_open_token_listing(true);
+        assert_eq!(contract.get_allowed_tokens(), vec![accounts(2).to_string()]);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(STORE_CODE_DEPOSIT)
+            .build());
+        contract.storage_deposit(None, None);
+        testing_env!(context.attached_deposit(0).build());
+        contract.register_token(accounts(4));
+
+        let metadata = serde_json::json!({
+            "spec": "ft-1.0.0",
+            "name": "Token",
+            "symbol": "TKN",
+            "icon": null,
+            "reference": null,
+            "reference_hash": null,
+            "decimals": 24,
+        });
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            VMConfig::default(),
+            RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(serde_json::to_vec(&metadata).unwrap())]
+        );
+        let balance = contract.storage_deposits.get(accounts(3).as_ref()).unwrap();
+        assert!(contract.on_ft_metadata(accounts(4).into(), accounts(3).into()));
+        assert!(contract.is_token_allowed(accounts(4)));
+        assert!(contract.storage_deposits.get(accounts(3).as_ref()).unwrap() < balance);
+
+        testing_env!(
+            context.build(),
+            VMConfig::default(),
+            RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"{}".to_vec())]
+        );
+        assert!(!contract.on_ft_metadata(accounts(5).into(), accounts(3).into()));
+        assert!(!contract.is_token_allowed(accounts(5)));
+    }
+
+    // Uploads the DAO and token code and launches a DAO with a new token as accounts(3).
+    fn create_test_dao_with_token(context: &mut VMContextBuilder, contract: &mut DaoFactory) -> DaoArgs {
+        store_test_code(context, contract, vec![1u8; 100]);
+        let mut vm_context = context.build();
+        vm_context.input = vec![2u8; 100];
+        testing_env!(vm_context);
+        contract.store_token_code();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(STORE_CODE_DEPOSIT)
+            .build());
+        let token_metadata = FungibleTokenMetadata {
+            spec: "ft-1.0.0".to_string(),
+            name: "Test".to_string(),
+            symbol: "TEST".to_string(),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: 24,
+        };
+        contract.create_dao_with_token(dao_args("test").metadata, token_metadata, 1000.into());
+        contract.get_dao("test".to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_create_dao_with_token_failed() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        let args = create_test_dao_with_token(&mut context, &mut contract);
+        assert_eq!(args.token_contract_id, format!("test-token.{}", accounts(0)));
+        assert!(contract.is_token_allowed(args.token_contract_id.clone().try_into().unwrap()));
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::default(),
+            RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        let dao_balance = contract.get_min_attached_balance(&args, 100);
+        let token_balance = (100 + EXTRA_BYTES) as Balance * STORAGE_PRICE_PER_BYTE;
+        contract.on_create_token(
+            "test".to_string(),
+            accounts(3).into(),
+            dao_balance.into(),
+            token_balance.into(),
+        );
+        assert!(contract.get_dao("test".to_string()).is_none());
+        assert!(!contract.is_token_allowed(args.token_contract_id.try_into().unwrap()));
+        assert_eq!(
+            contract.storage_deposits.get(accounts(3).as_ref()).unwrap(),
+            STORE_CODE_DEPOSIT - contract.storage_balance_cost
+        );
+    }
+
+    #[test]
+    fn test_create_dao_with_token_delisted_before_deploy() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        let args = create_test_dao_with_token(&mut context, &mut contract);
+        let dao_balance = contract.get_min_attached_balance(&args, 100);
+        testing_env!(context.attached_deposit(0).build());
+        contract.delist_dao("test".to_string());
+        let balance = contract.storage_deposits.get(accounts(3).as_ref()).unwrap();
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::default(),
+            RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        let token_balance = (100 + EXTRA_BYTES) as Balance * STORAGE_PRICE_PER_BYTE;
+        match contract.on_create_token(
+            "test".to_string(),
+            accounts(3).into(),
+            dao_balance.into(),
+            token_balance.into(),
+        ) {
+            near_sdk::PromiseOrValue::Value(created) => assert!(!created),
+            near_sdk::PromiseOrValue::Promise(_) => panic!("delisted DAO was deployed"),
+        }
+        assert_eq!(
+            contract.storage_deposits.get(accounts(3).as_ref()).unwrap(),
+            balance + dao_balance
+        );
+    }
+
+    #[test]
+    fn test_create_dao_with_token_dao_failed() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        let args = create_test_dao_with_token(&mut context, &mut contract);
+        let dao_balance = contract.get_min_attached_balance(&args, 100);
+        let token_balance = (100 + EXTRA_BYTES) as Balance * STORAGE_PRICE_PER_BYTE;
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::default(),
+            RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.on_create_token(
+            "test".to_string(),
+            accounts(3).into(),
+            dao_balance.into(),
+            token_balance.into(),
+        );
+        assert!(contract.get_dao("test".to_string()).is_some());
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::default(),
+            RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        let initial_storage_usage = env::storage_usage();
+        assert!(!contract.on_create("test".to_string(), accounts(3).into(), dao_balance.into()));
+        let storage_balance_released =
+            Balance::from(initial_storage_usage - env::storage_usage()) * STORAGE_PRICE_PER_BYTE;
+        assert!(storage_balance_released > 0);
+        assert!(contract.get_dao("test".to_string()).is_none());
+        assert_eq!(
+            contract.storage_deposits.get(accounts(3).as_ref()).unwrap(),
+            STORE_CODE_DEPOSIT - contract.storage_balance_cost - token_balance
+                + storage_balance_released
+        );
+    }
+
+    #[test]
+    fn test_update_dao_metadata() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        create_test_dao(&mut context, &mut contract);
+
+        let mut metadata = dao_args("test").metadata;
+        metadata.name = "Renamed".to_string();
+        metadata.youtube = Some("https://youtube.com/test".to_string());
+        testing_env!(context
+            .predecessor_account_id(format!("test.{}", accounts(0)).try_into().unwrap())
+            .build());
+        contract.update_dao_metadata(metadata);
+
+        let args = contract.get_dao("test".to_string()).unwrap();
+        assert_eq!(args.metadata.youtube, Some("https://youtube.com/test".to_string()));
+        assert!(contract.search_daos_by_name("test".to_string(), 0, 10).is_empty());
+        assert_eq!(contract.search_daos_by_name("ren".to_string(), 0, 10).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "dao not found")]
+    fn test_update_dao_metadata_not_dao() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = DaoFactory::new(accounts(2), accounts(1));
+        create_test_dao(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.update_dao_metadata(dao_args("test").metadata);
+    }
 }
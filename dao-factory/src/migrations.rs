@@ -0,0 +1,228 @@
+use crate::*;
+
+// Version of the state layout written by this code.
+// 1: initial layout with a single token, DAOs are registered without code versions.
+// 2: stored DAO code versions and their sizes, DAO indexes, token allow-list and community
+//    token code; DAO metadata has a youtube link.
+pub const STATE_VERSION: u32 = 2;
+
+// `DaoArgs` layout of version 1.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldDaoArgs {
+    token_contract_id: OldAccountId,
+    metadata: OldDaoMetadata,
+    owner_id: AccountId,
+}
+
+// Factory state layout of version 1.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldDaoFactory {
+    pub daos: UnorderedMap<AccountId, OldDaoArgs>,
+    pub storage_deposits: LookupMap<AccountId, Balance>,
+    pub token_contract_id: ValidAccountId,
+    pub storage_balance_cost: Balance,
+}
+
+// Reads factory state written by any previous version of the contract.
+pub(crate) fn migrate_state(state: &[u8]) -> DaoFactory {
+    if let Ok(this) = DaoFactory::try_from_slice(state) {
+        assert_eq!(this.state_version, STATE_VERSION, "ERR_UNKNOWN_STATE_VERSION");
+        return this;
+    }
+    OldDaoFactory::try_from_slice(state)
+        .expect("ERR_UNKNOWN_STATE")
+        .into()
+}
+
+// Registered DAOs stay under their version 1 prefix and are moved to the registry in batches
+// by `migrate_daos`, to keep the migration itself within gas limits.
+// Migrated factories are owned by the factory account itself, which can hand them over
+// with `set_owner`.
+impl From<OldDaoFactory> for DaoFactory {
+    fn from(old: OldDaoFactory) -> Self {
+        let mut allowed_tokens = UnorderedSet::new(StorageKey::AllowedTokens);
+        allowed_tokens.insert(old.token_contract_id.as_ref());
+        Self {
+            daos: UnorderedMap::new(StorageKey::RegisteredDaos),
+            storage_deposits: old.storage_deposits,
+            storage_balance_cost: old.storage_balance_cost,
+            dao_codes: UnorderedMap::new(StorageKey::DaoCodes),
+            dao_code_sizes: LookupMap::new(StorageKey::DaoCodeSizes),
+            default_code_hash: None,
+            owner_id: env::current_account_id(),
+            daos_by_owner: LookupMap::new(StorageKey::DaosByOwner),
+            daos_by_token: LookupMap::new(StorageKey::DaosByToken),
+            daos_by_name: TreeMap::new(StorageKey::DaosByName),
+            allowed_tokens,
+            open_token_listing: false,
+            token_code: LazyOption::new(StorageKey::TokenCode, None),
+            token_code_size: 0,
+            pending_daos: if old.daos.is_empty() { None } else { Some(old.daos) },
+            state_version: STATE_VERSION,
+        }
+    }
+}
+
+impl DaoFactory {
+    pub(crate) fn assert_daos_migrated(&self) {
+        assert!(self.pending_daos.is_none(), "DAO registry is being migrated");
+    }
+}
+
+#[near_bindgen]
+impl DaoFactory {
+    // Moves up to `limit` DAOs registered by version 1 to the registry and its indexes.
+    // Returns true once all of them are moved. Only the owner can call it.
+    pub fn migrate_daos(&mut self, limit: u64) -> bool {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only owner can migrate daos"
+        );
+        let mut pending_daos = match self.pending_daos.take() {
+            Some(pending_daos) => pending_daos,
+            None => return true,
+        };
+        for _ in 0..limit {
+            if pending_daos.is_empty() {
+                break;
+            }
+            // Removing the last entry doesn't move any other.
+            let dao_id = pending_daos.keys_as_vector().get(pending_daos.len() - 1).unwrap();
+            let old = pending_daos.remove(&dao_id).unwrap();
+            let args = DaoArgs {
+                token_contract_id: old.token_contract_id,
+                metadata: old.metadata.into(),
+                owner_id: old.owner_id,
+                code_hash: None,
+            };
+            self.daos.insert(&dao_id, &args);
+            self.internal_index_dao(&dao_id, &args);
+        }
+        if !pending_daos.is_empty() {
+            self.pending_daos = Some(pending_daos);
+        }
+        self.pending_daos.is_none()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    use near_sdk::test_utils::{VMContextBuilder, accounts};
+    use near_sdk::{testing_env, MockedBlockchain};
+
+    fn get_context() -> VMContextBuilder {
+        let mut context = VMContextBuilder::new();
+        context
+            .current_account_id(accounts(0))
+            .predecessor_account_id(accounts(0));
+        context
+    }
+
+    // Writes state as a version 1 factory would have left it, with DAOs named after `symbols`
+    // owned by accounts(3) on the accounts(2) token.
+    fn write_v1_snapshot(symbols: &[&str]) {
+        let mut daos = UnorderedMap::new(StorageKey::Daos);
+        for symbol in symbols {
+            daos.insert(&symbol.to_string(), &OldDaoArgs {
+                token_contract_id: accounts(2).to_string(),
+                metadata: OldDaoMetadata {
+                    name: symbol.to_uppercase(),
+                    purpose: "Test".to_string(),
+                    thumbnail: "".to_string(),
+                    symbol: symbol.to_string(),
+                    facebook: None,
+                    twitter: None,
+                    discord: None,
+                    instagram: Some("https://instagram.com/test".to_string()),
+                },
+                owner_id: accounts(3).to_string(),
+            });
+        }
+        env::state_write(&OldDaoFactory {
+            daos,
+            storage_deposits: LookupMap::new(StorageKey::StorageDeposits),
+            token_contract_id: accounts(2),
+            storage_balance_cost: 0,
+        });
+    }
+
+    #[test]
+    fn test_migrate_from_v1() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(&["test", "other", "third"]);
+
+        let mut contract = DaoFactory::migrate();
+        assert_eq!(contract.state_version, STATE_VERSION);
+        assert_eq!(contract.owner_id, accounts(0).to_string());
+        assert_eq!(contract.allowed_tokens.to_vec(), vec![accounts(2).to_string()]);
+        assert_eq!(contract.get_number_of_daos(), 0);
+
+        assert!(!contract.migrate_daos(2));
+        assert_eq!(contract.get_number_of_daos(), 2);
+        assert!(contract.migrate_daos(2));
+        assert!(contract.pending_daos.is_none());
+        assert_eq!(contract.get_number_of_daos(), 3);
+        assert!(contract.migrate_daos(2));
+
+        let args = contract.get_dao("test".to_string()).unwrap();
+        assert_eq!(args.metadata.instagram, Some("https://instagram.com/test".to_string()));
+        assert_eq!(args.metadata.youtube, None);
+        assert_eq!(args.owner_id, accounts(3).to_string());
+        assert_eq!(args.code_hash, None);
+        assert_eq!(contract.get_daos_by_owner(accounts(3), 0, 10).len(), 3);
+        assert_eq!(contract.get_daos_by_token(accounts(2), 0, 10).len(), 3);
+        assert_eq!(contract.search_daos_by_name("oth".to_string(), 0, 10)[0].0, "other".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "dao was created by a previous factory version")]
+    fn test_upgrade_v1_dao() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(&["test"]);
+        let mut contract = DaoFactory::migrate();
+        contract.migrate_daos(10);
+
+        testing_env!(get_context().predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.upgrade_dao("test".to_string(), [7u8; 32].into());
+    }
+
+    #[test]
+    fn test_migrate_keeps_allowed_tokens() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(&[]);
+        let mut contract = DaoFactory::migrate();
+        assert!(contract.pending_daos.is_none());
+        contract.allowed_tokens.remove(accounts(2).as_ref());
+        contract.allowed_tokens.insert(accounts(4).as_ref());
+        env::state_write(&contract);
+
+        let contract = DaoFactory::migrate();
+        assert_eq!(contract.allowed_tokens.to_vec(), vec![accounts(4).to_string()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "DAO registry is being migrated")]
+    fn test_create_dao_during_migration() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(&["test"]);
+        let mut contract = DaoFactory::migrate();
+        contract.create_dao(serde_json::from_str(r#"{
+            "token_contract_id": "bob",
+            "metadata": {"name": "New", "purpose": "", "thumbnail": "", "symbol": "new"},
+            "owner_id": "charlie"
+        }"#).unwrap(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can migrate daos")]
+    fn test_migrate_daos_not_owner() {
+        testing_env!(get_context().build());
+        write_v1_snapshot(&["test"]);
+        let mut contract = DaoFactory::migrate();
+        testing_env!(get_context().predecessor_account_id(accounts(3)).build());
+        contract.migrate_daos(10);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::*;
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+use near_sdk::assert_one_yocto;
+
+impl DaoFactory {
+    // Adds deposit to the account's balance, registering the account first if needed.
+    // The registration cost is kept aside and isn't part of the available balance.
+    pub(crate) fn internal_storage_deposit(&mut self, account_id: &AccountId, deposit: Balance) {
+        if let Some(previous_balance) = self.storage_deposits.get(account_id) {
+            self.storage_deposits
+                .insert(account_id, &(previous_balance + deposit));
+        } else {
+            assert!(deposit >= self.storage_balance_cost, "Deposit is too low");
+            self.storage_deposits
+                .insert(account_id, &(deposit - self.storage_balance_cost));
+        }
+    }
+
+    fn internal_storage_balance_of(&self, account_id: &AccountId) -> Option<StorageBalance> {
+        self.storage_deposits
+            .get(account_id)
+            .map(|balance| StorageBalance {
+                total: (self.storage_balance_cost + balance).into(),
+                available: balance.into(),
+            })
+    }
+}
+
+#[near_bindgen]
+impl StorageManagement for DaoFactory {
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let deposit = env::attached_deposit();
+        let account_id = account_id
+            .map(|account_id| account_id.into())
+            .unwrap_or_else(env::predecessor_account_id);
+        if registration_only.unwrap_or(false) {
+            let refund = if self.storage_deposits.get(&account_id).is_some() {
+                deposit
+            } else {
+                assert!(deposit >= self.storage_balance_cost, "Deposit is too low");
+                self.internal_storage_deposit(&account_id, self.storage_balance_cost);
+                deposit - self.storage_balance_cost
+            };
+            if refund > 0 {
+                Promise::new(env::predecessor_account_id()).transfer(refund);
+            }
+        } else {
+            self.internal_storage_deposit(&account_id, deposit);
+        }
+        self.internal_storage_balance_of(&account_id).unwrap()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let balance = self
+            .storage_deposits
+            .get(&account_id)
+            .expect("Account is not registered");
+        let amount = amount.map(|amount| amount.0).unwrap_or(balance);
+        assert!(amount <= balance, "Not enough available balance");
+        self.storage_deposits.insert(&account_id, &(balance - amount));
+        if amount > 0 {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+        self.internal_storage_balance_of(&account_id).unwrap()
+    }
+
+    // Registry entries of created DAOs are paid from the account's deposit and stay until delisted,
+    // so force-unregistering is not supported and unregistering only returns the rest of the deposit.
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        assert!(!force.unwrap_or(false), "Force unregister is not supported");
+        let account_id = env::predecessor_account_id();
+        if let Some(balance) = self.storage_deposits.remove(&account_id) {
+            Promise::new(account_id).transfer(self.storage_balance_cost + balance);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: self.storage_balance_cost.into(),
+            max: None,
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
+        self.internal_storage_balance_of(account_id.as_ref())
+    }
+}
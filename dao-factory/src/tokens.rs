@@ -0,0 +1,112 @@
+use crate::*;
+use near_contract_standards::fungible_token::metadata::{FungibleTokenMetadata, FT_METADATA_SPEC};
+
+// Gas for reading metadata of a token contract and verifying it.
+const GAS_FOR_FT_METADATA: Gas = 10_000_000_000_000;
+const GAS_FOR_ON_FT_METADATA: Gas = 10_000_000_000_000;
+
+#[ext_contract(ext_token)]
+pub trait ExtToken {
+    fn ft_metadata(&self) -> FungibleTokenMetadata;
+}
+
+#[ext_contract(ext_self_tokens)]
+pub trait ExtDaoFactoryTokens {
+    fn on_ft_metadata(&mut self, token_contract_id: AccountId, account_id: AccountId) -> bool;
+}
+
+impl DaoFactory {
+    fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only owner can manage tokens"
+        );
+    }
+}
+
+#[near_bindgen]
+impl DaoFactory {
+    // Adds token contract to the allow-list. Only the factory owner can add tokens this way.
+    pub fn add_token(&mut self, token_contract_id: ValidAccountId) {
+        self.assert_owner();
+        self.allowed_tokens.insert(token_contract_id.as_ref());
+    }
+
+    // Removes token contract from the allow-list. Existing DAOs on this token are kept.
+    pub fn remove_token(&mut self, token_contract_id: ValidAccountId) {
+        self.assert_owner();
+        self.allowed_tokens.remove(token_contract_id.as_ref());
+    }
+
+    pub fn set_open_token_listing(&mut self, open_token_listing: bool) {
+        self.assert_owner();
+        self.open_token_listing = open_token_listing;
+    }
+
+    // Adds token contract to the allow-list when listing is open, once it's verified that
+    // the contract implements fungible token metadata. Storage is paid from the caller's storage deposit.
+    pub fn register_token(&mut self, token_contract_id: ValidAccountId) -> Promise {
+        assert!(self.open_token_listing, "Token listing is closed");
+        let account_id = env::predecessor_account_id();
+        assert!(
+            self.storage_deposits.get(&account_id).is_some(),
+            "Account is not registered"
+        );
+        assert!(
+            !self.allowed_tokens.contains(token_contract_id.as_ref()),
+            "Token is already allowed"
+        );
+        ext_token::ft_metadata(token_contract_id.as_ref(), 0, GAS_FOR_FT_METADATA).then(
+            ext_self_tokens::on_ft_metadata(
+                token_contract_id.into(),
+                account_id,
+                &env::current_account_id(),
+                0,
+                GAS_FOR_ON_FT_METADATA,
+            ),
+        )
+    }
+
+    #[private]
+    pub fn on_ft_metadata(&mut self, token_contract_id: AccountId, account_id: AccountId) -> bool {
+        let metadata = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                serde_json::from_slice::<FungibleTokenMetadata>(&value).ok()
+            }
+            _ => None,
+        };
+        if !metadata.iter().any(|metadata| metadata.spec == FT_METADATA_SPEC) {
+            return false;
+        }
+        let balance = match self.storage_deposits.get(&account_id) {
+            Some(balance) => balance,
+            None => return false,
+        };
+        let initial_storage_usage = env::storage_usage();
+        if !self.allowed_tokens.insert(&token_contract_id) {
+            return true;
+        }
+        let storage_cost =
+            Balance::from(env::storage_usage() - initial_storage_usage) * STORAGE_PRICE_PER_BYTE;
+        if balance < storage_cost {
+            self.allowed_tokens.remove(&token_contract_id);
+            return false;
+        }
+        self.storage_deposits
+            .insert(&account_id, &(balance - storage_cost));
+        true
+    }
+
+    pub fn get_allowed_tokens(&self) -> Vec<AccountId> {
+        self.allowed_tokens.to_vec()
+    }
+
+    pub fn is_token_allowed(&self, token_contract_id: ValidAccountId) -> bool {
+        self.allowed_tokens.contains(token_contract_id.as_ref())
+    }
+
+    pub fn is_open_token_listing(&self) -> bool {
+        self.open_token_listing
+    }
+}
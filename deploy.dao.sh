@@ -15,11 +15,11 @@ near deploy \
             "purpose": "Build the future with blockchain",
             "thumbnail": "https://pbs.twimg.com/profile_images/1470780411747844096/vpxt_095_400x400.jpg",
             "symbol": "CZB",
-            "facebook": "facebook.com",
-            "youtube": "youtube.com",
-            "twitter": "twitter.com",
-            "discord": "discord.com",
-            "instagram": "instagram.com"
+            "facebook": "https://facebook.com",
+            "youtube": "https://youtube.com",
+            "twitter": "https://twitter.com",
+            "discord": "https://discord.com",
+            "instagram": "https://instagram.com"
         },
         "token_contract_id": "connecus-token.manhndev.testnet",
         "owner_id": "manhndev.testnet"
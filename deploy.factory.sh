@@ -4,10 +4,27 @@ cd dao-factory
 
 sh build.sh
 
+cd ../connesus-dao
+
+sh build.sh
+
+cd ../community-token
+
+sh build.sh
+
 cd ..
 
 near deploy \
     --wasmFile out/connecus-factory.wasm \
     --initFunction "new" \
-    --initArgs '{"token_contract_id": "connecus.testnet"}' \
-    --accountId factory.connecus.testnet
\ No newline at end of file
+    --initArgs '{"token_contract_id": "connecus.testnet", "owner_id": "manhndev.testnet"}' \
+    --accountId factory.connecus.testnet
+
+# upload dao code, the first uploaded code becomes the default for new daos
+# wasm is sent from file as it is too large for command line arguments
+(cd scripts && npm install --silent)
+
+node scripts/upload-code.js factory.connecus.testnet store_code out/connecus-dao.wasm manhndev.testnet 5
+
+# upload community token code used by create_dao_with_token
+node scripts/upload-code.js factory.connecus.testnet store_token_code out/connecus-token.wasm manhndev.testnet 5
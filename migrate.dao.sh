@@ -9,5 +9,9 @@ cd ..
 near deploy \
     --wasmFile out/connecus-dao.wasm \
     --initFunction "migrate" \
-    --initArgs '{"owner_id": "manhndev.testnet"}' \
-    --accountId connecus-dao.manhndev.testnet
\ No newline at end of file
+    --initArgs '{}' \
+    --accountId connecus-dao.manhndev.testnet
+
+# index proposals left by the previous version, 100 per call
+until near call connecus-dao.manhndev.testnet migrate_indexes '{"limit": 100}' \
+    --accountId manhndev.testnet --gas 300000000000000 | tail -1 | grep -q true; do :; done
@@ -9,5 +9,13 @@ cd ..
 near deploy \
     --wasmFile out/connecus-factory.wasm \
     --initFunction "migrate" \
-    --initArgs '{"token_contract_id": "connecus.testnet"}' \
-    --accountId factory.connecus.testnet
\ No newline at end of file
+    --initArgs '{}' \
+    --accountId factory.connecus.testnet
+
+# move daos registered by the previous version to the registry, 50 per call
+until near call factory.connecus.testnet migrate_daos '{"limit": 50}' \
+    --accountId factory.connecus.testnet --gas 300000000000000 | tail -1 | grep -q true; do :; done
+
+# hand the factory over from its own account to the owner
+near call factory.connecus.testnet set_owner '{"owner_id": "manhndev.testnet"}' \
+    --accountId factory.connecus.testnet --depositYocto 1
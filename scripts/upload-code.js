@@ -0,0 +1,39 @@
+// Calls a factory method with the contents of a wasm file as raw arguments, e.g. `store_code`.
+// near-cli takes arguments on the command line, which fails for files above ~96 KiB.
+//
+// Usage: node scripts/upload-code.js <contract id> <method> <wasm file> <signer id> [deposit in NEAR]
+// Keys are read from ~/.near-credentials, network is taken from NEAR_ENV (testnet by default).
+const fs = require('fs');
+const os = require('os');
+const path = require('path');
+const { connect, keyStores, utils } = require('near-api-js');
+
+const GAS = '300000000000000';
+
+async function main() {
+    const [contractId, methodName, wasmFile, signerId, deposit = '5'] = process.argv.slice(2);
+    if (!signerId) {
+        console.error('Usage: node scripts/upload-code.js <contract id> <method> <wasm file> <signer id> [deposit in NEAR]');
+        process.exit(1);
+    }
+    const networkId = process.env.NEAR_ENV || 'testnet';
+    const near = await connect({
+        networkId,
+        keyStore: new keyStores.UnencryptedFileSystemKeyStore(path.join(os.homedir(), '.near-credentials')),
+        nodeUrl: `https://rpc.${networkId}.near.org`,
+    });
+    const account = await near.account(signerId);
+    const outcome = await account.functionCall({
+        contractId,
+        methodName,
+        args: fs.readFileSync(wasmFile),
+        gas: GAS,
+        attachedDeposit: utils.format.parseNearAmount(deposit),
+    });
+    console.log(Buffer.from(outcome.status.SuccessValue || '', 'base64').toString());
+}
+
+main().catch((error) => {
+    console.error(error);
+    process.exit(1);
+});